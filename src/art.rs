@@ -1,26 +1,189 @@
+use crate::client::MalojaClient;
 use crate::credentials::MalojaCredentials;
 use crate::errors::RequestError;
-use crate::{get_client_async, parse_headers};
+use crate::json::{AddPictureReq, AddPictureRes};
+use crate::server::Capability;
+use crate::{block_on, handle_response, require_field};
+use base64::Engine;
 use bytes::Bytes;
 use reqwest::Client;
+use std::path::PathBuf;
 
-async fn get_image_async(
-    id: String,
-    from_type: &str,
-    credentials: MalojaCredentials,
-    client: Client,
-) -> Result<Bytes, RequestError> {
-    let response = client
-        .get(credentials.get_url() + "/image?" + from_type + "_id=" + &id)
-        .headers(parse_headers(credentials.headers))
+/// The largest image, in bytes, which is downloaded for an [ImageSource::Url].
+pub const MAX_IMAGE_SIZE: usize = 20 * 1024 * 1024;
+
+/// An image to upload as artist or album art.
+#[derive(Debug, Clone)]
+pub enum ImageSource {
+    /// The image file's contents.
+    Bytes(Bytes),
+    /// A path to an image file.
+    File(PathBuf),
+    /// The URL of an image, which is downloaded and then uploaded to the server.
+    /// Images larger than [MAX_IMAGE_SIZE] give [RequestError::ImageTooLarge].
+    Url(String),
+}
+
+// maloja only takes the image's MIME type from the data URL, so it is detected from the file's signature.
+fn image_mime(image: &[u8]) -> Option<&'static str> {
+    match image {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some("image/gif"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        _ => None,
+    }
+}
+
+// The image could be hosted anywhere, so unlike requests to the server, the download never
+// skips certificate verification, even if the credentials do.
+async fn download_image_async(url: String) -> Result<Bytes, RequestError> {
+    let client = Client::builder()
+        .build()
+        .map_err(RequestError::TransportError)?;
+    let mut response = client
+        .get(url)
         .send()
-        .await;
-    match response {
-        Err(err) => Err(RequestError::ReqwestError(err)),
-        Ok(response) => match response.error_for_status() {
-            Err(err) => Err(RequestError::ServerError(err.to_string())),
-            Ok(actual_response) => Ok(actual_response.bytes().await.unwrap()),
-        },
+        .await
+        .map_err(RequestError::TransportError)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(RequestError::StatusError {
+            status,
+            body: response.text().await.unwrap_or_default(),
+        });
+    }
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_IMAGE_SIZE as u64)
+    {
+        return Err(RequestError::ImageTooLarge);
+    }
+    let mut image = vec![];
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(RequestError::TransportError)?
+    {
+        if image.len() + chunk.len() > MAX_IMAGE_SIZE {
+            return Err(RequestError::ImageTooLarge);
+        }
+        image.extend_from_slice(&chunk);
+    }
+    Ok(Bytes::from(image))
+}
+
+impl MalojaClient {
+    async fn get_image_async(&self, id: String, from_type: &str) -> Result<Bytes, RequestError> {
+        let response = self
+            .get(&("/image?".to_owned() + from_type + "_id=" + &id))
+            .send()
+            .await;
+        match response {
+            Err(err) => Err(RequestError::TransportError(err)),
+            Ok(response) => match response.status() {
+                status if !status.is_success() => Err(RequestError::StatusError {
+                    status,
+                    body: response.text().await.unwrap_or_default(),
+                }),
+                _ => response.bytes().await.map_err(RequestError::TransportError),
+            },
+        }
+    }
+
+    async fn image_bytes_async(&self, image: ImageSource) -> Result<Bytes, RequestError> {
+        match image {
+            ImageSource::Bytes(bytes) => Ok(bytes),
+            ImageSource::File(path) => tokio::fs::read(path)
+                .await
+                .map(Bytes::from)
+                .map_err(RequestError::IoError),
+            ImageSource::Url(url) => download_image_async(url).await,
+        }
+    }
+
+    async fn set_image_async(
+        &self,
+        image: ImageSource,
+        artist_id: Option<String>,
+        album_id: Option<String>,
+    ) -> Result<String, RequestError> {
+        let key = self.api_key()?;
+        let image = self.image_bytes_async(image).await?;
+        let mime = image_mime(&image).ok_or(RequestError::UnsupportedImage)?;
+        let requestbody = AddPictureReq {
+            b64: format!(
+                "data:{};base64,{}",
+                mime,
+                base64::engine::general_purpose::STANDARD.encode(&image)
+            ),
+            artist_id,
+            album_id,
+            key,
+        };
+        let response = self
+            .post("/apis/mlj_1/addpicture")
+            .json(&requestbody)
+            .send()
+            .await;
+        require_field(handle_response::<AddPictureRes>(response).await?.url, "url")
+    }
+
+    /// See [MalojaClient::album_art].
+    pub async fn album_art_async(&self, id: String) -> Result<Bytes, RequestError> {
+        self.gated(Capability::Albums, self.get_image_async(id, "album"))
+            .await
+    }
+
+    /// See [MalojaClient::artist_art].
+    pub async fn artist_art_async(&self, id: String) -> Result<Bytes, RequestError> {
+        self.get_image_async(id, "artist").await
+    }
+
+    /// See [MalojaClient::set_album_art].
+    pub async fn set_album_art_async(
+        &self,
+        id: String,
+        image: ImageSource,
+    ) -> Result<String, RequestError> {
+        self.gated(
+            Capability::Albums,
+            self.set_image_async(image, None, Some(id)),
+        )
+        .await
+    }
+
+    /// See [MalojaClient::set_artist_art].
+    pub async fn set_artist_art_async(
+        &self,
+        id: String,
+        image: ImageSource,
+    ) -> Result<String, RequestError> {
+        self.set_image_async(image, Some(id), None).await
+    }
+
+    /// Fetches album art for a certain album, given its ID.
+    pub fn album_art(&self, id: String) -> Result<Bytes, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.album_art_async(id).await })
+    }
+
+    /// Fetches artist art for a certain artist, given its ID.
+    pub fn artist_art(&self, id: String) -> Result<Bytes, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.artist_art_async(id).await })
+    }
+
+    /// Uploads album art for a certain album, given its ID. See [set_album_art].
+    pub fn set_album_art(&self, id: String, image: ImageSource) -> Result<String, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.set_album_art_async(id, image).await })
+    }
+
+    /// Uploads artist art for a certain artist, given its ID. See [set_artist_art].
+    pub fn set_artist_art(&self, id: String, image: ImageSource) -> Result<String, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.set_artist_art_async(id, image).await })
     }
 }
 
@@ -30,35 +193,153 @@ pub async fn album_art_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<Bytes, RequestError> {
-    get_image_async(id, "album", credentials, client).await
+    MalojaClient::with_client(credentials, client)
+        .album_art_async(id)
+        .await
 }
 
-/// See [artist_art_async].
+/// See [artist_art].
 pub async fn artist_art_async(
     id: String,
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<Bytes, RequestError> {
-    get_image_async(id, "artist", credentials, client).await
-}
-
-fn get_image(
-    id: String,
-    from_type: &str,
-    credentials: MalojaCredentials,
-) -> Result<Bytes, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        get_image_async(id, from_type, credentials, client.unwrap()).await
-    })
+    MalojaClient::with_client(credentials, client)
+        .artist_art_async(id)
+        .await
 }
 
 /// Fetches album art for a certain album, given its ID.
 pub fn album_art(id: String, credentials: MalojaCredentials) -> Result<Bytes, RequestError> {
-    get_image(id, "album", credentials)
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .album_art(id)
 }
 
 /// Fetches artist art for a certain artist, given its ID.
 pub fn artist_art(id: String, credentials: MalojaCredentials) -> Result<Bytes, RequestError> {
-    get_image(id, "artist", credentials)
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .artist_art(id)
+}
+
+/// See [set_album_art].
+pub async fn set_album_art_async(
+    id: String,
+    image: ImageSource,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<String, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .set_album_art_async(id, image)
+        .await
+}
+
+/// See [set_artist_art].
+pub async fn set_artist_art_async(
+    id: String,
+    image: ImageSource,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<String, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .set_artist_art_async(id, image)
+        .await
+}
+
+/// Uploads album art for a certain album, given its ID. Requires an API key.
+/// Returns the URL the server now serves the image at.
+pub fn set_album_art(
+    id: String,
+    image: ImageSource,
+    credentials: MalojaCredentials,
+) -> Result<String, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .set_album_art(id, image)
+}
+
+/// Uploads artist art for a certain artist, given its ID. Requires an API key.
+/// Returns the URL the server now serves the image at.
+///
+/// The image can be a JPEG, PNG, GIF, or WebP file, and is rejected with
+/// [RequestError::UnsupportedImage] before uploading otherwise.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::art::ImageSource;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// mljcl::art::set_artist_art("12".to_string(), ImageSource::File("portrait.jpg".into()), creds.clone()).unwrap();
+/// mljcl::art::set_artist_art("13".to_string(), ImageSource::Url("https://example.com/portrait.png".into()), creds).unwrap();
+/// ```
+pub fn set_artist_art(
+    id: String,
+    image: ImageSource,
+    credentials: MalojaCredentials,
+) -> Result<String, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .set_artist_art(id, image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    #[test]
+    fn image_mime_from_signature() {
+        assert_eq!(
+            image_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some("image/png")
+        );
+        assert_eq!(image_mime(b"\xFF\xD8\xFF\xE0"), Some("image/jpeg"));
+        assert_eq!(image_mime(b"GIF89a"), Some("image/gif"));
+        assert_eq!(image_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(image_mime(b"<svg></svg>"), None);
+        assert_eq!(image_mime(b""), None);
+    }
+
+    #[test]
+    fn downloaded_image_is_uploaded() {
+        // The mock server only sends text, and GIF files start with a text signature.
+        let image = "GIF89a rest of the image";
+        let (image_server, _) = serve(vec![(200, image)]);
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{"status":"success","url":"/images/artists/12.gif"}"#,
+        )]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let url = client
+            .set_artist_art(
+                "12".to_string(),
+                ImageSource::Url(image_server.get_url() + "/portrait.gif"),
+            )
+            .unwrap();
+        assert_eq!(url, "/images/artists/12.gif");
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /apis/mlj_1/addpicture "));
+        assert!(request.contains(&format!(
+            "data:image/gif;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(image.as_bytes())
+        )));
+    }
+
+    #[test]
+    fn downloads_are_capped() {
+        let image = "x".repeat(MAX_IMAGE_SIZE + 1);
+        let (image_server, _) = serve(vec![(200, &image)]);
+        let (credentials, requests) = serve(vec![]);
+        let client = MalojaClient::new(credentials).unwrap();
+        assert!(matches!(
+            client.set_artist_art(
+                "12".to_string(),
+                ImageSource::Url(image_server.get_url() + "/huge.png"),
+            ),
+            Err(RequestError::ImageTooLarge)
+        ));
+        assert!(requests.recv().is_err());
+    }
 }
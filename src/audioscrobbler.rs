@@ -0,0 +1,479 @@
+use crate::credentials::MalojaCredentials;
+use crate::errors::{BatchError, RequestError};
+use crate::json::{GetMobileSessionRes, TrackScrobbleRes, UpdateNowPlayingRes};
+use crate::submission::ScrobbleSubmission;
+use crate::{
+    block_on, get_client_async, parse_error, parse_headers, parse_response, require_field,
+};
+use reqwest::{header::HeaderMap, Client};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+/// The most scrobbles a single `track.scrobble` request may contain.
+pub const MAX_BATCH_SIZE: usize = 50;
+
+// maloja doesn't check the API key or secret, but the protocol requires them.
+const MALOJA_API_KEY: &str = "mljcl";
+const MALOJA_SHARED_SECRET: &str = "mljcl";
+
+/// How many scrobbles a server accepted and ignored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScrobbleCounts {
+    /// Scrobbles the server recorded.
+    pub accepted: u64,
+    /// Scrobbles the server received but chose not to record, e.g. because they were too old.
+    pub ignored: u64,
+}
+
+fn scrobble_counts(response: TrackScrobbleRes, submitted: usize) -> ScrobbleCounts {
+    let attr = response.scrobbles.and_then(|scrobbles| scrobbles.attr);
+    let count = |value: Option<serde_json::Value>| {
+        value.and_then(|value| match value {
+            serde_json::Value::String(count) => count.parse().ok(),
+            value => value.as_u64(),
+        })
+    };
+    let (accepted, ignored) = match attr {
+        None => (None, None),
+        Some(attr) => (count(attr.accepted), count(attr.ignored)),
+    };
+    let ignored = ignored.unwrap_or(0);
+    ScrobbleCounts {
+        // maloja only reports how many scrobbles it ignored.
+        accepted: accepted.unwrap_or((submitted as u64).saturating_sub(ignored)),
+        ignored,
+    }
+}
+
+/// A client for the Audioscrobbler 2.0 protocol, which is the scrobbling part of the Last.fm API.
+///
+/// maloja serves this protocol at `/apis/audioscrobbler`, but the client works with any
+/// Last.fm-compatible server. Scrobbling requires a session, started with
+/// [AudioscrobblerClient::authenticate] or restored with [AudioscrobblerClient::with_session_key].
+/// Cloning an `AudioscrobblerClient` is cheap, and the clones share the same session.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::audioscrobbler::AudioscrobblerClient;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let client = AudioscrobblerClient::from_credentials(&creds).unwrap();
+/// // maloja accepts any username, and takes one of its API keys as the password.
+/// client.authenticate("me".into(), "key".into()).unwrap();
+/// # let submissions = vec![];
+/// let counts = client.scrobble(submissions).unwrap();
+/// println!("{} scrobbles accepted", counts.accepted);
+/// ```
+#[derive(Debug, Clone)]
+pub struct AudioscrobblerClient {
+    url: String,
+    api_key: String,
+    shared_secret: String,
+    client: Client,
+    headers: HeaderMap,
+    session_key: Arc<RwLock<Option<String>>>,
+}
+
+impl AudioscrobblerClient {
+    /// Creates an `AudioscrobblerClient` for the API at `url`, e.g. `https://ws.audioscrobbler.com/2.0/`,
+    /// given an application's API key and shared secret.
+    pub fn new(
+        url: String,
+        api_key: String,
+        shared_secret: String,
+    ) -> Result<AudioscrobblerClient, reqwest::Error> {
+        Ok(AudioscrobblerClient::with_client(
+            url,
+            api_key,
+            shared_secret,
+            Client::builder().build()?,
+        ))
+    }
+
+    /// Creates an `AudioscrobblerClient` which sends its requests through an existing `reqwest::Client`.
+    pub fn with_client(
+        url: String,
+        api_key: String,
+        shared_secret: String,
+        client: Client,
+    ) -> AudioscrobblerClient {
+        AudioscrobblerClient {
+            url,
+            api_key,
+            shared_secret,
+            client,
+            headers: HeaderMap::new(),
+            session_key: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Creates an `AudioscrobblerClient` for a maloja server's Audioscrobbler API,
+    /// sending the credentials' headers along with every request.
+    pub fn from_credentials(
+        credentials: &MalojaCredentials,
+    ) -> Result<AudioscrobblerClient, reqwest::Error> {
+        let mut client = AudioscrobblerClient::with_client(
+            credentials.get_url() + "/apis/audioscrobbler/2.0/",
+            MALOJA_API_KEY.to_string(),
+            MALOJA_SHARED_SECRET.to_string(),
+            get_client_async(credentials)?,
+        );
+        client.headers = parse_headers(credentials.headers.clone());
+        Ok(client)
+    }
+
+    /// Uses a session key from an earlier [AudioscrobblerClient::authenticate], instead of starting a new session.
+    pub fn with_session_key(self, session_key: String) -> AudioscrobblerClient {
+        self.set_session_key(Some(session_key));
+        self
+    }
+
+    /// The key of the current session, if one has been started.
+    pub fn session_key(&self) -> Option<String> {
+        self.session_key.read().ok()?.clone()
+    }
+
+    fn set_session_key(&self, session_key: Option<String>) {
+        if let Ok(mut current) = self.session_key.write() {
+            *current = session_key;
+        }
+    }
+
+    fn require_session_key(&self) -> Result<String, RequestError> {
+        self.session_key().ok_or(RequestError::MissingSession)
+    }
+
+    // Every parameter except `format` is signed: the parameters are sorted by name and
+    // concatenated as names and values, followed by the shared secret, then hashed with MD5.
+    fn sign(&self, params: &BTreeMap<String, String>) -> String {
+        let mut signature = String::new();
+        for (name, value) in params {
+            signature += name;
+            signature += value;
+        }
+        signature += &self.shared_secret;
+        format!("{:x}", md5::compute(signature))
+    }
+
+    async fn call_async<T: crate::json::MalojaResponse + for<'de> serde::Deserialize<'de>>(
+        &self,
+        method: &str,
+        params: Vec<(String, String)>,
+    ) -> Result<T, RequestError> {
+        let mut params: BTreeMap<String, String> = params.into_iter().collect();
+        params.insert("method".to_string(), method.to_string());
+        params.insert("api_key".to_string(), self.api_key.clone());
+        params.insert("api_sig".to_string(), self.sign(&params));
+        params.insert("format".to_string(), "json".to_string());
+        let response = self
+            .client
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .form(&params)
+            .send()
+            .await
+            .map_err(RequestError::TransportError)?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(RequestError::TransportError)?;
+        // Errors which mean the session or the credentials are bad get their own variants,
+        // since retrying the request won't help until they are fixed. maloja sends them with
+        // 401 or 403, so the code is checked before the status is.
+        match parse_error(&body) {
+            Some(error) if error._type == "9" => {
+                self.set_session_key(None);
+                Err(RequestError::MissingSession)
+            }
+            Some(error) if error._type == "4" || error._type == "13" => {
+                Err(RequestError::Unauthorized(error.desc))
+            }
+            _ => parse_response(status, body),
+        }
+    }
+
+    /// See [AudioscrobblerClient::authenticate].
+    pub async fn authenticate_async(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<String, RequestError> {
+        let response = self
+            .call_async::<GetMobileSessionRes>(
+                "auth.getMobileSession",
+                vec![
+                    ("username".to_string(), username),
+                    ("password".to_string(), password),
+                ],
+            )
+            .await?;
+        let session_key = require_field(response.session, "session")?.key;
+        self.set_session_key(Some(session_key.clone()));
+        Ok(session_key)
+    }
+
+    /// Starts a session with `auth.getMobileSession`, which is used by every later request.
+    /// Returns the session key, which can be stored and reused with [AudioscrobblerClient::with_session_key].
+    pub fn authenticate(&self, username: String, password: String) -> Result<String, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.authenticate_async(username, password).await })
+    }
+
+    /// See [AudioscrobblerClient::scrobble].
+    pub async fn scrobble_async(
+        &self,
+        submissions: Vec<ScrobbleSubmission>,
+    ) -> Result<ScrobbleCounts, BatchError<ScrobbleCounts>> {
+        let mut counts = ScrobbleCounts::default();
+        for (index, batch) in submissions.chunks(MAX_BATCH_SIZE).enumerate() {
+            match self.scrobble_batch_async(batch).await {
+                Ok(batch_counts) => {
+                    counts.accepted += batch_counts.accepted;
+                    counts.ignored += batch_counts.ignored;
+                }
+                Err(error) => {
+                    return Err(BatchError {
+                        completed: counts,
+                        batch: index,
+                        error,
+                    })
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn scrobble_batch_async(
+        &self,
+        batch: &[ScrobbleSubmission],
+    ) -> Result<ScrobbleCounts, RequestError> {
+        let mut params = vec![("sk".to_string(), self.require_session_key()?)];
+        for (index, submission) in batch.iter().enumerate() {
+            params.extend(
+                submission
+                    .clone()
+                    .into_audioscrobbler_params(Some(index), true),
+            );
+        }
+        let response = self
+            .call_async::<TrackScrobbleRes>("track.scrobble", params)
+            .await?;
+        Ok(scrobble_counts(response, batch.len()))
+    }
+
+    /// Submits scrobbles with `track.scrobble`, in batches of up to [MAX_BATCH_SIZE].
+    /// Scrobbles without a time are given the current time.
+    ///
+    /// If a batch fails, later batches aren't sent, and the error says which batch failed along
+    /// with the counts of the batches before it. If the server ended the session, the error is
+    /// [RequestError::MissingSession] and a new [AudioscrobblerClient::authenticate] is needed.
+    pub fn scrobble(
+        &self,
+        submissions: Vec<ScrobbleSubmission>,
+    ) -> Result<ScrobbleCounts, BatchError<ScrobbleCounts>> {
+        let client = self.clone();
+        block_on(async move { client.scrobble_async(submissions).await })
+    }
+
+    /// See [AudioscrobblerClient::update_now_playing].
+    pub async fn update_now_playing_async(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<(), RequestError> {
+        self.send_now_playing_async(submission, false).await
+    }
+
+    // `track.updateNowPlaying` has no timestamp parameter, but servers ignore parameters they
+    // don't know, so one can be sent along for those which want to know when the track started.
+    pub(crate) async fn send_now_playing_async(
+        &self,
+        submission: ScrobbleSubmission,
+        with_timestamp: bool,
+    ) -> Result<(), RequestError> {
+        let mut params = vec![("sk".to_string(), self.require_session_key()?)];
+        params.extend(submission.into_audioscrobbler_params(None, with_timestamp));
+        self.call_async::<UpdateNowPlayingRes>("track.updateNowPlaying", params)
+            .await?;
+        Ok(())
+    }
+
+    /// Reports the track currently playing with `track.updateNowPlaying`. The submission's time is ignored.
+    pub fn update_now_playing(&self, submission: ScrobbleSubmission) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.update_now_playing_async(submission).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+    use chrono::DateTime;
+    use std::sync::mpsc::Receiver;
+
+    fn client(responses: Vec<(u16, &str)>) -> (AudioscrobblerClient, Receiver<String>) {
+        let (credentials, requests) = serve(responses);
+        let client = AudioscrobblerClient::from_credentials(&credentials)
+            .unwrap()
+            .with_session_key("SESSION".to_string());
+        (client, requests)
+    }
+
+    // The form parameters of a request.
+    fn params(request: &str) -> BTreeMap<String, String> {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        reqwest::Url::parse(&format!("http://localhost/?{}", body))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    fn submissions(count: usize) -> Vec<ScrobbleSubmission> {
+        (0..count)
+            .map(|index| {
+                ScrobbleSubmission::builder()
+                    .title(format!("Track {}", index))
+                    .artist("Artist".to_string())
+                    .time(DateTime::from_timestamp(1_700_000_000 + index as i64, 0).unwrap())
+                    .build()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sign_sorts_parameters_and_appends_the_secret() {
+        let client = AudioscrobblerClient::with_client(
+            "http://localhost/".to_string(),
+            "key".to_string(),
+            "secret".to_string(),
+            Client::new(),
+        );
+        let params = BTreeMap::from([
+            ("timestamp[0]".to_string(), "1700000000".to_string()),
+            ("sk".to_string(), "SESSION".to_string()),
+            ("method".to_string(), "track.scrobble".to_string()),
+            ("api_key".to_string(), "key".to_string()),
+        ]);
+        assert_eq!(client.sign(&params), "60be959791075573ca764e58cdd5ccdd");
+    }
+
+    #[test]
+    fn scrobble_counts_from_responses() {
+        let response = |json: &str| serde_json::from_str::<TrackScrobbleRes>(json).unwrap();
+        let lastfm = response(r#"{"scrobbles":{"@attr":{"accepted":2,"ignored":1}}}"#);
+        assert_eq!(
+            scrobble_counts(lastfm, 3),
+            ScrobbleCounts {
+                accepted: 2,
+                ignored: 1
+            }
+        );
+        let maloja = response(r#"{"scrobbles":{"@attr":{"ignored":"1"}}}"#);
+        assert_eq!(
+            scrobble_counts(maloja, 3),
+            ScrobbleCounts {
+                accepted: 2,
+                ignored: 1
+            }
+        );
+        let bare = response(r#"{"scrobbles":{}}"#);
+        assert_eq!(
+            scrobble_counts(bare, 3),
+            ScrobbleCounts {
+                accepted: 3,
+                ignored: 0
+            }
+        );
+    }
+
+    #[test]
+    fn scrobbles_are_split_into_batches() {
+        let accepted = r#"{"scrobbles":{"@attr":{"ignored":0}}}"#;
+        let (client, requests) = client(vec![(200, accepted), (200, accepted)]);
+        let counts = client.scrobble(submissions(MAX_BATCH_SIZE + 2)).unwrap();
+        assert_eq!(counts.accepted, MAX_BATCH_SIZE as u64 + 2);
+        let first = params(&requests.recv().unwrap());
+        assert_eq!(first["method"], "track.scrobble");
+        assert_eq!(first["sk"], "SESSION");
+        assert_eq!(first["format"], "json");
+        assert_eq!(first[&format!("track[{}]", MAX_BATCH_SIZE - 1)], "Track 49");
+        assert!(!first.contains_key(&format!("track[{}]", MAX_BATCH_SIZE)));
+        let second = params(&requests.recv().unwrap());
+        assert_eq!(second["track[0]"], "Track 50");
+        assert_eq!(second["timestamp[1]"], "1700000051");
+        assert!(!second.contains_key("track[2]"));
+    }
+
+    #[test]
+    fn failed_batch_reports_earlier_counts() {
+        let (client, _) = client(vec![
+            (
+                200,
+                r#"{"scrobbles":{"@attr":{"accepted":49,"ignored":1}}}"#,
+            ),
+            (500, "<html>Internal Server Error</html>"),
+        ]);
+        let error = client
+            .scrobble(submissions(MAX_BATCH_SIZE * 3))
+            .unwrap_err();
+        assert_eq!(error.batch, 1);
+        assert_eq!(
+            error.completed,
+            ScrobbleCounts {
+                accepted: 49,
+                ignored: 1
+            }
+        );
+        assert!(matches!(error.error, RequestError::StatusError { .. }));
+    }
+
+    #[test]
+    fn invalid_session_ends_the_session() {
+        let (client, _) = client(vec![(
+            403,
+            r#"{"error":9,"message":"Invalid session key - Please re-authenticate"}"#,
+        )]);
+        let error = client.scrobble(submissions(1)).unwrap_err();
+        assert!(matches!(error.error, RequestError::MissingSession));
+        assert_eq!(client.session_key(), None);
+    }
+
+    #[test]
+    fn authentication_errors_are_unauthorized() {
+        for code in [4, 13] {
+            let body = format!(r#"{{"error":{},"message":"Authentication failed"}}"#, code);
+            let (authenticating, _) = client(vec![(403, &body)]);
+            assert!(matches!(
+                authenticating.authenticate("me".to_string(), "wrong".to_string()),
+                Err(RequestError::Unauthorized(_))
+            ));
+            for status in [200, 401] {
+                let (client, _) = client(vec![(status, &body)]);
+                let error = client.scrobble(submissions(1)).unwrap_err();
+                assert!(matches!(
+                    error.error,
+                    RequestError::Unauthorized(desc) if desc == "Authentication failed"
+                ));
+                assert_eq!(client.session_key(), Some("SESSION".to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn authenticate_stores_the_session_key() {
+        let (client, requests) = client(vec![(200, r#"{"session":{"name":"me","key":"NEW"}}"#)]);
+        let key = client
+            .authenticate("me".to_string(), "password".to_string())
+            .unwrap();
+        assert_eq!(key, "NEW");
+        assert_eq!(client.session_key(), Some("NEW".to_string()));
+        let params = params(&requests.recv().unwrap());
+        assert_eq!(params["method"], "auth.getMobileSession");
+        assert_eq!(params["username"], "me");
+        assert_eq!(params["api_sig"].len(), 32);
+    }
+}
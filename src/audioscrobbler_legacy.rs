@@ -0,0 +1,428 @@
+use crate::credentials::MalojaCredentials;
+use crate::errors::{BatchError, RequestError};
+use crate::submission::ScrobbleSubmission;
+use crate::{block_on, get_client_async, parse_headers};
+use chrono::Utc;
+use reqwest::{header::HeaderMap, Client, StatusCode};
+use std::sync::{Arc, RwLock};
+
+/// The most scrobbles a single submission may contain.
+pub const MAX_BATCH_SIZE: usize = 50;
+
+const LEGACY_PATH: &str = "/apis/audioscrobbler_legacy/";
+
+/// A session from a 1.2 handshake, along with where to send requests during it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacySession {
+    /// The session ID, sent with every request.
+    pub session_id: String,
+    /// Where now playing notifications are sent.
+    pub now_playing_url: String,
+    /// Where scrobbles are submitted.
+    pub submission_url: String,
+}
+
+/// A client for the legacy Audioscrobbler 1.2 submission protocol, which maloja serves at
+/// `/apis/audioscrobbler_legacy`.
+///
+/// A session is started with [LegacyAudioscrobblerClient::handshake], using any username and
+/// one of maloja's API keys as the password. Cloning a `LegacyAudioscrobblerClient` is cheap,
+/// and the clones share the same session.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::audioscrobbler_legacy::LegacyAudioscrobblerClient;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// # let submission = mljcl::submission::ScrobbleSubmission::builder().title("Some track".into()).artist("Some artist".into()).build().unwrap();
+/// let client = LegacyAudioscrobblerClient::from_credentials(&creds).unwrap();
+/// client.handshake("me".into(), "key".into()).unwrap();
+/// client.submit(vec![submission]).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct LegacyAudioscrobblerClient {
+    url: String,
+    client_id: String,
+    client_version: String,
+    client: Client,
+    headers: HeaderMap,
+    session: Arc<RwLock<Option<LegacySession>>>,
+}
+
+impl LegacyAudioscrobblerClient {
+    /// Creates a `LegacyAudioscrobblerClient` for a maloja server, along with a new `reqwest::Client`.
+    pub fn from_credentials(
+        credentials: &MalojaCredentials,
+    ) -> Result<LegacyAudioscrobblerClient, reqwest::Error> {
+        Ok(LegacyAudioscrobblerClient::with_client(
+            credentials,
+            get_client_async(credentials)?,
+        ))
+    }
+
+    /// Creates a `LegacyAudioscrobblerClient` for a maloja server which sends its requests
+    /// through an existing `reqwest::Client`.
+    pub fn with_client(
+        credentials: &MalojaCredentials,
+        client: Client,
+    ) -> LegacyAudioscrobblerClient {
+        LegacyAudioscrobblerClient {
+            url: credentials.get_url(),
+            client_id: "mlj".to_string(),
+            client_version: env!("CARGO_PKG_VERSION").to_string(),
+            client,
+            headers: parse_headers(credentials.headers.clone()),
+            session: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Sets the client ID and version sent in the handshake, for bridging another player's scrobbles.
+    pub fn with_client_id(
+        mut self,
+        client_id: String,
+        client_version: String,
+    ) -> LegacyAudioscrobblerClient {
+        self.client_id = client_id;
+        self.client_version = client_version;
+        self
+    }
+
+    /// The current session, if a handshake has succeeded.
+    pub fn session(&self) -> Option<LegacySession> {
+        self.session.read().ok()?.clone()
+    }
+
+    fn set_session(&self, session: Option<LegacySession>) {
+        if let Ok(mut current) = self.session.write() {
+            *current = session;
+        }
+    }
+
+    // maloja answers the handshake with URLs built from its own idea of its address, which is
+    // wrong when it is behind a proxy or reached through another name, so they are moved onto
+    // the address from the credentials.
+    fn rebase(&self, url: &str) -> String {
+        match url.find(LEGACY_PATH) {
+            Some(index) => self.url.clone() + &url[index..],
+            None => url.to_string(),
+        }
+    }
+
+    // Responses are plain text: a status on the first line, followed by any data.
+    async fn handle_text_response(
+        &self,
+        response: Result<reqwest::Response, reqwest::Error>,
+    ) -> Result<Vec<String>, RequestError> {
+        let response = response.map_err(RequestError::TransportError)?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(RequestError::TransportError)?;
+        if !status.is_success() {
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                    RequestError::Unauthorized(body.trim().to_string())
+                }
+                _ => RequestError::StatusError { status, body },
+            });
+        }
+        let lines: Vec<String> = body.lines().map(|line| line.trim().to_string()).collect();
+        let first = lines.first().map(String::as_str).unwrap_or_default();
+        let (code, reason) = first.split_once(' ').unwrap_or((first, ""));
+        match code {
+            "OK" => Ok(lines),
+            "BADAUTH" => Err(RequestError::Unauthorized("BADAUTH".to_string())),
+            "BADSESSION" => {
+                self.set_session(None);
+                Err(RequestError::MissingSession)
+            }
+            "BANNED" | "BADTIME" | "FAILED" => Err(RequestError::ServerError {
+                error_type: code.to_string(),
+                desc: reason.to_string(),
+            }),
+            _ => Err(RequestError::MalformedResponse(format!(
+                "unknown status `{}`",
+                first
+            ))),
+        }
+    }
+
+    /// See [LegacyAudioscrobblerClient::handshake].
+    pub async fn handshake_async(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<LegacySession, RequestError> {
+        let timestamp = Utc::now().timestamp().to_string();
+        let password_hash = format!("{:x}", md5::compute(password));
+        let token = format!("{:x}", md5::compute(password_hash + &timestamp));
+        let response = self
+            .client
+            .get(self.url.clone() + LEGACY_PATH)
+            .headers(self.headers.clone())
+            .query(&[
+                ("hs", "true"),
+                ("p", "1.2"),
+                ("c", &self.client_id),
+                ("v", &self.client_version),
+                ("u", &username),
+                ("t", &timestamp),
+                ("a", &token),
+            ])
+            .send()
+            .await;
+        let lines = self.handle_text_response(response).await?;
+        let line = |index: usize, name: &str| {
+            lines.get(index).cloned().ok_or_else(|| {
+                RequestError::MalformedResponse(format!("handshake did not include {}", name))
+            })
+        };
+        let session = LegacySession {
+            session_id: line(1, "a session ID")?,
+            now_playing_url: self.rebase(&line(2, "a now playing URL")?),
+            submission_url: self.rebase(&line(3, "a submission URL")?),
+        };
+        self.set_session(Some(session.clone()));
+        Ok(session)
+    }
+
+    /// Starts a session, using the token authentication of the 1.2 protocol.
+    /// With maloja, the password is one of its API keys.
+    pub fn handshake(
+        &self,
+        username: String,
+        password: String,
+    ) -> Result<LegacySession, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.handshake_async(username, password).await })
+    }
+
+    fn require_session(&self) -> Result<LegacySession, RequestError> {
+        self.session().ok_or(RequestError::MissingSession)
+    }
+
+    /// See [LegacyAudioscrobblerClient::now_playing].
+    pub async fn now_playing_async(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<(), RequestError> {
+        let session = self.require_session()?;
+        let params = [
+            ("s", session.session_id),
+            ("a", submission.artists.join(", ")),
+            ("t", submission.title),
+            ("b", submission.album.unwrap_or_default()),
+            (
+                "l",
+                submission
+                    .length
+                    .map(|length| length.as_secs().to_string())
+                    .unwrap_or_default(),
+            ),
+            ("n", String::new()),
+            ("m", String::new()),
+        ];
+        let response = self
+            .client
+            .post(session.now_playing_url)
+            .headers(self.headers.clone())
+            .form(&params)
+            .send()
+            .await;
+        self.handle_text_response(response).await?;
+        Ok(())
+    }
+
+    /// Reports the track currently playing. The submission's time is ignored.
+    pub fn now_playing(&self, submission: ScrobbleSubmission) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.now_playing_async(submission).await })
+    }
+
+    /// See [LegacyAudioscrobblerClient::submit].
+    pub async fn submit_async(
+        &self,
+        submissions: Vec<ScrobbleSubmission>,
+    ) -> Result<(), BatchError<usize>> {
+        let mut submitted = 0;
+        for (index, batch) in submissions.chunks(MAX_BATCH_SIZE).enumerate() {
+            if let Err(error) = self.submit_batch_async(batch).await {
+                return Err(BatchError {
+                    completed: submitted,
+                    batch: index,
+                    error,
+                });
+            }
+            submitted += batch.len();
+        }
+        Ok(())
+    }
+
+    async fn submit_batch_async(&self, batch: &[ScrobbleSubmission]) -> Result<(), RequestError> {
+        let session = self.require_session()?;
+        let mut params = vec![("s".to_string(), session.session_id)];
+        for (index, submission) in batch.iter().enumerate() {
+            params.extend(submission.clone().into_legacy_params(index));
+        }
+        let response = self
+            .client
+            .post(&session.submission_url)
+            .headers(self.headers.clone())
+            .form(&params)
+            .send()
+            .await;
+        self.handle_text_response(response).await?;
+        Ok(())
+    }
+
+    /// Submits scrobbles in batches of up to [MAX_BATCH_SIZE]. Scrobbles without a time are given the current time.
+    ///
+    /// If a batch fails, later batches aren't sent, and the error says which batch failed along
+    /// with how many scrobbles the batches before it contained. If the server ended the session,
+    /// the error is [RequestError::MissingSession] and a new handshake is needed.
+    pub fn submit(&self, submissions: Vec<ScrobbleSubmission>) -> Result<(), BatchError<usize>> {
+        let client = self.clone();
+        block_on(async move { client.submit_async(submissions).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+    use std::collections::BTreeMap;
+    use std::sync::mpsc::Receiver;
+    use std::time::Duration;
+
+    fn client(responses: Vec<(u16, &str)>) -> (LegacyAudioscrobblerClient, Receiver<String>) {
+        let (credentials, requests) = serve(responses);
+        (
+            LegacyAudioscrobblerClient::from_credentials(&credentials).unwrap(),
+            requests,
+        )
+    }
+
+    // The query or form parameters of a request.
+    fn params(request: &str) -> BTreeMap<String, String> {
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let target = head.split(' ').nth(1).unwrap();
+        let query = target.split_once('?').map_or(body, |(_, query)| query);
+        reqwest::Url::parse(&format!("http://localhost/?{}", query))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    fn session(client: &LegacyAudioscrobblerClient) {
+        client.set_session(Some(LegacySession {
+            session_id: "SESSION".to_string(),
+            now_playing_url: client.url.clone() + LEGACY_PATH + "nowplaying",
+            submission_url: client.url.clone() + LEGACY_PATH + "scrobble",
+        }));
+    }
+
+    fn submissions(count: usize) -> Vec<ScrobbleSubmission> {
+        (0..count)
+            .map(|index| {
+                ScrobbleSubmission::builder()
+                    .title(format!("Track {}", index))
+                    .artist("Artist".to_string())
+                    .build()
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn handshake_sends_a_token_and_rebases_urls() {
+        let (client, requests) = client(vec![(
+            200,
+            "OK\nSESSION\nhttp://maloja.internal:42010/apis/audioscrobbler_legacy/nowplaying\nhttp://maloja.internal:42010/apis/audioscrobbler_legacy/scrobble\n",
+        )]);
+        let session = client
+            .handshake("me".to_string(), "password".to_string())
+            .unwrap();
+        let params = params(&requests.recv().unwrap());
+        assert_eq!(params["hs"], "true");
+        assert_eq!(params["p"], "1.2");
+        assert_eq!(params["u"], "me");
+        let password_hash = format!("{:x}", md5::compute("password"));
+        let token = format!("{:x}", md5::compute(password_hash + &params["t"]));
+        assert_eq!(params["a"], token);
+        assert_eq!(session.session_id, "SESSION");
+        assert_eq!(
+            session.submission_url,
+            client.url.clone() + "/apis/audioscrobbler_legacy/scrobble"
+        );
+        assert_eq!(client.session(), Some(session));
+    }
+
+    #[test]
+    fn incomplete_handshake_is_malformed() {
+        let (client, _) = client(vec![(200, "OK\nSESSION\n")]);
+        assert!(matches!(
+            client.handshake("me".to_string(), "password".to_string()),
+            Err(RequestError::MalformedResponse(_))
+        ));
+        assert_eq!(client.session(), None);
+    }
+
+    #[test]
+    fn text_responses() {
+        let (client, _) = client(vec![
+            (200, "BADAUTH\n"),
+            (200, "FAILED Plugin error\n"),
+            (200, "<html></html>"),
+            (200, "BADSESSION\n"),
+        ]);
+        session(&client);
+        let now_playing = || client.now_playing(submissions(1).remove(0));
+        assert!(matches!(now_playing(), Err(RequestError::Unauthorized(_))));
+        assert!(matches!(
+            now_playing(),
+            Err(RequestError::ServerError { error_type, desc })
+                if error_type == "FAILED" && desc == "Plugin error"
+        ));
+        assert!(matches!(
+            now_playing(),
+            Err(RequestError::MalformedResponse(_))
+        ));
+        assert!(client.session().is_some());
+        assert!(matches!(now_playing(), Err(RequestError::MissingSession)));
+        assert_eq!(client.session(), None);
+    }
+
+    #[test]
+    fn source_is_unknown_without_a_length() {
+        let (client, requests) = client(vec![(200, "OK\n")]);
+        session(&client);
+        let mut submissions = submissions(2);
+        submissions[1].length = Some(Duration::from_secs(200));
+        client.submit(submissions).unwrap();
+        let params = params(&requests.recv().unwrap());
+        assert_eq!(params["s"], "SESSION");
+        assert_eq!(params["o[0]"], "U");
+        assert_eq!(params["l[0]"], "");
+        assert_eq!(params["o[1]"], "P");
+        assert_eq!(params["l[1]"], "200");
+    }
+
+    #[test]
+    fn failed_batch_reports_earlier_scrobbles() {
+        let (client, requests) = client(vec![(200, "OK\n"), (200, "BADSESSION\n")]);
+        session(&client);
+        let error = client
+            .submit(submissions(MAX_BATCH_SIZE * 2 + 1))
+            .unwrap_err();
+        assert_eq!(error.batch, 1);
+        assert_eq!(error.completed, MAX_BATCH_SIZE);
+        assert!(matches!(error.error, RequestError::MissingSession));
+        let first = params(&requests.recv().unwrap());
+        assert_eq!(first[&format!("t[{}]", MAX_BATCH_SIZE - 1)], "Track 49");
+        assert!(!first.contains_key(&format!("t[{}]", MAX_BATCH_SIZE)));
+        assert_eq!(params(&requests.recv().unwrap())["t[0]"], "Track 50");
+    }
+}
@@ -1,6 +1,7 @@
+use crate::client::MalojaClient;
 use crate::errors::RequestError;
-use crate::full_query_path;
-use crate::{get_client_async, handle_response, parse_headers, MalojaCredentials};
+use crate::server::Capability;
+use crate::{block_on, full_query_path, handle_response, require_field, MalojaCredentials};
 use crate::{
     json::*,
     range::{process_range, Range},
@@ -29,42 +30,143 @@ pub struct AlbumChart {
     pub albums: Vec<(Album, u64)>,
 }
 
+impl MalojaClient {
+    /// See [MalojaClient::charts_artists].
+    pub async fn charts_artists_async(&self, range: Range) -> Result<ArtistChart, RequestError> {
+        let from_until_in = process_range(range)?;
+        let requestbody = ArtistChartReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/charts/artists")?)
+            .send()
+            .await;
+        match handle_response::<ArtistChartRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut artists: Vec<(Artist, u64)> = vec![];
+                for artist in require_field(response.list, "list")? {
+                    artists.push((
+                        Artist {
+                            name: artist.artist,
+                            id: artist.artist_id.to_string(),
+                        },
+                        artist.rank,
+                    ));
+                }
+                Ok(ArtistChart { artists })
+            }
+        }
+    }
+
+    /// Fetches a ranked list of most listened artists, given a time frame. See [charts_artists].
+    pub fn charts_artists(&self, range: Range) -> Result<ArtistChart, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.charts_artists_async(range).await })
+    }
+
+    /// See [MalojaClient::charts_tracks].
+    pub async fn charts_tracks_async(
+        &self,
+        range: Range,
+        artist: Option<String>,
+    ) -> Result<TrackChart, RequestError> {
+        let from_until_in = process_range(range)?;
+        let requestbody = TrackChartReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+            artist,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/charts/tracks")?)
+            .send()
+            .await;
+        match handle_response::<TrackChartRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut tracks: Vec<(Track, u64)> = vec![];
+                for track in require_field(response.list, "list")? {
+                    tracks.push((Track::from_trackresultres(track.clone()), track.rank));
+                }
+                Ok(TrackChart { tracks })
+            }
+        }
+    }
+
+    /// Fetches a ranked list of most listened tracks, optionally from a given artist, given a time frame.
+    /// See [charts_tracks].
+    pub fn charts_tracks(
+        &self,
+        range: Range,
+        artist: Option<String>,
+    ) -> Result<TrackChart, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.charts_tracks_async(range, artist).await })
+    }
+
+    /// See [MalojaClient::charts_albums].
+    pub async fn charts_albums_async(
+        &self,
+        range: Range,
+        artist: Option<String>,
+    ) -> Result<AlbumChart, RequestError> {
+        self.gated(Capability::Albums, async {
+            let from_until_in = process_range(range)?;
+            let requestbody = AlbumChartReq {
+                from: from_until_in.0,
+                until: from_until_in.1,
+                _in: from_until_in.2,
+                artist,
+            };
+            let response = self
+                .get(&full_query_path(requestbody, "/apis/mlj_1/charts/albums")?)
+                .send()
+                .await;
+            match handle_response::<AlbumChartRes>(response).await {
+                Err(error) => Err(error),
+                Ok(response) => {
+                    let mut albums: Vec<(Album, u64)> = vec![];
+                    for album in require_field(response.list, "list")? {
+                        albums.push((
+                            Album {
+                                name: album.album.albumtitle,
+                                id: album.album_id.to_string(),
+                                artists: album.album.artists,
+                            },
+                            album.rank,
+                        ));
+                    }
+                    Ok(AlbumChart { albums })
+                }
+            }
+        })
+        .await
+    }
+
+    /// Fetches a ranked list of most listened albums, optionally from a given artist, given a time frame.
+    /// See [charts_albums].
+    pub fn charts_albums(
+        &self,
+        range: Range,
+        artist: Option<String>,
+    ) -> Result<AlbumChart, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.charts_albums_async(range, artist).await })
+    }
+}
+
 /// See [charts_artists].
 pub async fn charts_artists_async(
     range: Range,
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<ArtistChart, RequestError> {
-    let from_until_in = process_range(range);
-    let requestbody = ArtistChartReq {
-        from: from_until_in.0,
-        until: from_until_in.1,
-        _in: from_until_in.2,
-    };
-    let response = client
-        .get(full_query_path(
-            requestbody,
-            &(credentials.get_url() + "/apis/mlj_1/charts/artists"),
-        ))
-        .headers(parse_headers(credentials.headers))
-        .send()
-        .await;
-    match handle_response::<ArtistChartRes>(response).await {
-        Err(error) => Err(error),
-        Ok(response) => {
-            let mut artists: Vec<(Artist, u64)> = vec![];
-            for artist in response.list.unwrap() {
-                artists.push((
-                    Artist {
-                        name: artist.artist,
-                        id: artist.artist_id.to_string(),
-                    },
-                    artist.rank,
-                ));
-            }
-            Ok(ArtistChart { artists })
-        }
-    }
+    MalojaClient::with_client(credentials, client)
+        .charts_artists_async(range)
+        .await
 }
 
 /// Fetches a ranked list of most listened artists, given a time frame.
@@ -75,9 +177,10 @@ pub async fn charts_artists_async(
 /// * `credentials` - Your credentials.
 ///
 /// # Examples
-/// ```
-/// use mljcl::Range;
+/// ```no_run
+/// use mljcl::range::Range;
 ///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 /// let mut top_artists_ranked = mljcl::charts::charts_artists(Range::AllTime, creds).unwrap().artists;
 /// top_artists_ranked.truncate(3);
 /// let top_artists: Vec<String> = top_artists_ranked
@@ -90,10 +193,9 @@ pub fn charts_artists(
     range: Range,
     credentials: MalojaCredentials,
 ) -> Result<ArtistChart, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        charts_artists_async(range, credentials, client.unwrap()).await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .charts_artists(range)
 }
 
 /// See [charts_tracks].
@@ -103,31 +205,9 @@ pub async fn charts_tracks_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<TrackChart, RequestError> {
-    let from_until_in = process_range(range);
-    let requestbody = TrackChartReq {
-        from: from_until_in.0,
-        until: from_until_in.1,
-        _in: from_until_in.2,
-        artist,
-    };
-    let response = client
-        .get(full_query_path(
-            requestbody,
-            &(credentials.get_url() + "/apis/mlj_1/charts/tracks"),
-        ))
-        .headers(parse_headers(credentials.headers))
-        .send()
-        .await;
-    match handle_response::<TrackChartRes>(response).await {
-        Err(error) => Err(error),
-        Ok(response) => {
-            let mut tracks: Vec<(Track, u64)> = vec![];
-            for track in response.list.unwrap() {
-                tracks.push((Track::from_trackresultres(track.clone()), track.rank));
-            }
-            Ok(TrackChart { tracks })
-        }
-    }
+    MalojaClient::with_client(credentials, client)
+        .charts_tracks_async(range, artist)
+        .await
 }
 
 /// Fetches a ranked list of most listened tracks, optionally from a given artist, given a time frame.
@@ -139,10 +219,12 @@ pub async fn charts_tracks_async(
 /// * `credentials` - Your credentials.
 ///
 /// # Examples
-/// ```
-/// use mljcl::Range;
+/// ```no_run
+/// use mljcl::range::Range;
 ///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 /// let mut top_tracks_ranked = mljcl::charts::charts_tracks(Range::AllTime, None, creds).unwrap().tracks;
+/// top_tracks_ranked.truncate(3);
 /// let top_tracks: Vec<String> = top_tracks_ranked
 ///         .into_iter()
 ///         .map(|(track, _)| track.name)
@@ -154,10 +236,9 @@ pub fn charts_tracks(
     artist: Option<String>,
     credentials: MalojaCredentials,
 ) -> Result<TrackChart, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        charts_tracks_async(range, artist, credentials, client.unwrap()).await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .charts_tracks(range, artist)
 }
 
 /// See [charts_albums].
@@ -167,38 +248,9 @@ pub async fn charts_albums_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<AlbumChart, RequestError> {
-    let from_until_in = process_range(range);
-    let requestbody = AlbumChartReq {
-        from: from_until_in.0,
-        until: from_until_in.1,
-        _in: from_until_in.2,
-        artist,
-    };
-    let response = client
-        .get(full_query_path(
-            requestbody,
-            &(credentials.get_url() + "/apis/mlj_1/charts/albums"),
-        ))
-        .headers(parse_headers(credentials.headers))
-        .send()
-        .await;
-    match handle_response::<AlbumChartRes>(response).await {
-        Err(error) => Err(error),
-        Ok(response) => {
-            let mut albums: Vec<(Album, u64)> = vec![];
-            for album in response.list.unwrap() {
-                albums.push((
-                    Album {
-                        name: album.album.albumtitle,
-                        id: album.album_id.to_string(),
-                        artists: album.album.artists,
-                    },
-                    album.rank,
-                ));
-            }
-            Ok(AlbumChart { albums })
-        }
-    }
+    MalojaClient::with_client(credentials, client)
+        .charts_albums_async(range, artist)
+        .await
 }
 
 /// Fetches a ranked list of most listened albums, optionally from a given artist, given a time frame.
@@ -210,9 +262,10 @@ pub async fn charts_albums_async(
 /// * `credentials` - Your credentials.
 ///
 /// # Examples
-/// ```
-/// use mljcl::Range;
+/// ```no_run
+/// use mljcl::range::Range;
 ///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 /// let mut top_albums_ranked = mljcl::charts::charts_albums(Range::AllTime, None, creds).unwrap().albums;
 /// top_albums_ranked.truncate(3);
 /// let top_albums: Vec<String> = top_albums_ranked
@@ -226,8 +279,44 @@ pub fn charts_albums(
     artist: Option<String>,
     credentials: MalojaCredentials,
 ) -> Result<AlbumChart, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        charts_albums_async(range, artist, credentials, client.unwrap()).await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .charts_albums(range, artist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    fn client(responses: Vec<(u16, &str)>) -> MalojaClient {
+        MalojaClient::new(serve(responses).0).unwrap()
+    }
+
+    #[test]
+    fn chart_without_list_is_malformed() {
+        let client = client(vec![(200, r#"{"status":"ok"}"#)]);
+        assert!(matches!(
+            client.charts_artists(Range::AllTime),
+            Err(RequestError::MalformedResponse(message)) if message.contains("`list`")
+        ));
+    }
+
+    #[test]
+    fn chart_ranks_are_kept() {
+        let client = client(vec![(
+            200,
+            r#"{"status":"ok","list":[
+                {"scrobbles":3,"real_scrobbles":3,"artist":"A","artist_id":7,"rank":1,"associated_artists":[]},
+                {"scrobbles":1,"real_scrobbles":1,"artist":"B","artist_id":2,"rank":2,"associated_artists":[]}
+            ]}"#,
+        )]);
+        let chart = client.charts_artists(Range::AllTime).unwrap();
+        let ranks: Vec<(&str, &str, u64)> = chart
+            .artists
+            .iter()
+            .map(|(artist, rank)| (artist.name.as_str(), artist.id.as_str(), *rank))
+            .collect();
+        assert_eq!(ranks, [("A", "7", 1), ("B", "2", 2)]);
+    }
 }
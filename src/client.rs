@@ -0,0 +1,118 @@
+use crate::credentials::MalojaCredentials;
+use crate::errors::RequestError;
+use crate::server::ServerInfo;
+use crate::{get_client_async, parse_headers};
+use reqwest::{header::HeaderMap, Client, RequestBuilder};
+use std::sync::{Arc, RwLock};
+
+/// Which of maloja's APIs a [MalojaClient] submits scrobbles through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrobbleProtocol {
+    /// maloja's own API, at `/apis/mlj_1`.
+    #[default]
+    Native,
+    /// maloja's ListenBrainz-compatible API, at `/apis/listenbrainz`.
+    /// Useful when only that API is reachable, e.g. through a proxy.
+    ///
+    /// ListenBrainz has nowhere to put how long a track was listened to, so a submission's
+    /// `duration` isn't sent. Its response only confirms that the listen arrived, so the
+    /// [crate::json::ScrobbleRes] returned for it is made up from the submission.
+    ListenBrainz,
+}
+
+/// A handle to a maloja server which owns its credentials and a reusable connection pool.
+///
+/// Every endpoint mljcl supports is available as a method, both as an async variant
+/// (suffixed with `_async`) and a blocking one. Cloning a `MalojaClient` is cheap and
+/// the clones share the same connection pool, as well as what they know about the server.
+///
+/// # Examples
+///
+/// ```no_run
+/// let client = mljcl::credentials::MalojaCredentialsBuilder::new()
+///     .ip("127.0.0.1".into())
+///     .port(42010)
+///     .build_client()
+///     .unwrap();
+/// let scrobbles = client.numscrobbles(None, mljcl::range::Range::AllTime).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct MalojaClient {
+    credentials: MalojaCredentials,
+    client: Client,
+    url: String,
+    headers: HeaderMap,
+    server_info: Arc<RwLock<Option<ServerInfo>>>,
+    protocol: ScrobbleProtocol,
+}
+
+impl MalojaClient {
+    /// Creates a `MalojaClient` along with a new `reqwest::Client` built by [get_client_async].
+    pub fn new(credentials: MalojaCredentials) -> Result<MalojaClient, reqwest::Error> {
+        let client = get_client_async(&credentials)?;
+        Ok(MalojaClient::with_client(credentials, client))
+    }
+
+    /// Creates a `MalojaClient` which sends its requests through an existing `reqwest::Client`.
+    pub fn with_client(credentials: MalojaCredentials, client: Client) -> MalojaClient {
+        MalojaClient {
+            url: credentials.get_url(),
+            headers: parse_headers(credentials.headers.clone()),
+            credentials,
+            client,
+            server_info: Arc::new(RwLock::new(None)),
+            protocol: ScrobbleProtocol::Native,
+        }
+    }
+
+    /// Sets which API scrobbles are submitted through. [ScrobbleProtocol::Native] is used by default.
+    pub fn with_protocol(mut self, protocol: ScrobbleProtocol) -> MalojaClient {
+        self.protocol = protocol;
+        self
+    }
+
+    /// The API this client submits scrobbles through.
+    pub fn protocol(&self) -> ScrobbleProtocol {
+        self.protocol
+    }
+
+    /// The credentials this client was created with.
+    pub fn credentials(&self) -> &MalojaCredentials {
+        &self.credentials
+    }
+
+    /// The underlying `reqwest::Client`.
+    pub fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The server's information, if it has been fetched by [MalojaClient::server_info] before.
+    pub fn cached_server_info(&self) -> Option<ServerInfo> {
+        self.server_info.read().ok()?.clone()
+    }
+
+    pub(crate) fn cache_server_info(&self, info: ServerInfo) {
+        if let Ok(mut cached) = self.server_info.write() {
+            *cached = Some(info);
+        }
+    }
+
+    pub(crate) fn api_key(&self) -> Result<String, RequestError> {
+        self.credentials
+            .api_key
+            .clone()
+            .ok_or(RequestError::MissingApiKey)
+    }
+
+    pub(crate) fn get(&self, path: &str) -> RequestBuilder {
+        self.client
+            .get(self.url.clone() + path)
+            .headers(self.headers.clone())
+    }
+
+    pub(crate) fn post(&self, path: &str) -> RequestBuilder {
+        self.client
+            .post(self.url.clone() + path)
+            .headers(self.headers.clone())
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use crate::client::MalojaClient;
 use crate::errors::MalojaCredentialsBuilderError;
 
 /// A set of credentials for a maloja server.
@@ -133,4 +134,9 @@ impl MalojaCredentialsBuilder {
             api_key: self.api_key,
         })
     }
+
+    /// Builds the credentials and wraps them in a [MalojaClient] with its own connection pool.
+    pub fn build_client(self) -> Result<MalojaClient, MalojaCredentialsBuilderError> {
+        MalojaClient::new(self.build()?).map_err(MalojaCredentialsBuilderError::ClientError)
+    }
 }
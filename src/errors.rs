@@ -1,10 +1,134 @@
+use crate::server::{Capability, Version};
+use reqwest::StatusCode;
+use std::fmt;
+
 /// A request error from a `mljcl` function.
 #[derive(Debug)]
 pub enum RequestError {
-    /// An error from the `reqwest` crate when making an HTTP request.
-    ReqwestError(reqwest::Error),
+    /// The request could not be sent, or its response could not be received.
+    TransportError(reqwest::Error),
+    /// The server responded with an unsuccessful HTTP status and no maloja error.
+    StatusError {
+        /// The HTTP status of the response.
+        status: StatusCode,
+        /// The body of the response.
+        body: String,
+    },
     /// A serverside error in maloja.
-    ServerError(String),
+    ServerError {
+        /// maloja's name for the kind of error.
+        error_type: String,
+        /// maloja's description of the error.
+        desc: String,
+    },
+    /// The response was not the JSON mljcl expected.
+    DecodeError {
+        /// Why the response could not be decoded.
+        error: serde_json::Error,
+        /// The body of the response.
+        body: String,
+    },
+    /// The response was valid JSON, but was missing data or contained impossible values.
+    MalformedResponse(String),
+    /// The query parameters for the request could not be encoded.
+    EncodeError(serde_qs::Error),
+    /// The request requires an API key, but the credentials don't have one.
+    MissingApiKey,
+    /// The request requires an Audioscrobbler session, but none has been started, or the server ended it.
+    MissingSession,
+    /// The time range can't be used for a request.
+    InvalidRange(String),
+    /// The server refused the request's authentication, usually because the API key is wrong.
+    Unauthorized(String),
+    /// An artist, track, or album has no ID, or one that isn't a number.
+    InvalidId(String),
+    /// A function for one kind of entity was given another kind, e.g. a track to `artist_info`.
+    WrongEntityKind {
+        /// The kind the function takes: "artist", "track" or "album".
+        expected: &'static str,
+        /// The kind it was given.
+        found: &'static str,
+    },
+    /// A file to upload could not be read.
+    IoError(std::io::Error),
+    /// An image to upload isn't in a format maloja accepts.
+    UnsupportedImage,
+    /// An image to download and then upload is larger than `art::MAX_IMAGE_SIZE`.
+    ImageTooLarge,
+    /// A blocking function couldn't run its request, because the runtime it runs requests on
+    /// couldn't be started or the request's task panicked.
+    RuntimeError(String),
+    /// The server's maloja version is too old for the request.
+    Unsupported {
+        /// The feature the request needed.
+        capability: Capability,
+        /// The server's version.
+        version: Version,
+    },
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::TransportError(error) => write!(f, "request failed: {}", error),
+            RequestError::StatusError { status, .. } => {
+                write!(f, "server responded with status {}", status)
+            }
+            RequestError::ServerError { error_type, desc } => {
+                write!(f, "maloja error ({}): {}", error_type, desc)
+            }
+            RequestError::DecodeError { error, .. } => {
+                write!(f, "could not decode response: {}", error)
+            }
+            RequestError::MalformedResponse(reason) => write!(f, "malformed response: {}", reason),
+            RequestError::EncodeError(error) => write!(f, "could not encode query: {}", error),
+            RequestError::MissingApiKey => write!(f, "no API key was provided"),
+            RequestError::MissingSession => write!(f, "no Audioscrobbler session was started"),
+            RequestError::InvalidRange(reason) => write!(f, "invalid range: {}", reason),
+            RequestError::Unauthorized(desc) => write!(f, "unauthorized: {}", desc),
+            RequestError::InvalidId(reason) => write!(f, "invalid ID: {}", reason),
+            RequestError::WrongEntityKind { expected, found } => {
+                write!(f, "expected {} but got {}", expected, found)
+            }
+            RequestError::IoError(error) => write!(f, "could not read file: {}", error),
+            RequestError::UnsupportedImage => {
+                write!(f, "image is not a JPEG, PNG, GIF, or WebP file")
+            }
+            RequestError::ImageTooLarge => write!(f, "image is too large to download"),
+            RequestError::RuntimeError(reason) => write!(f, "could not run request: {}", reason),
+            RequestError::Unsupported {
+                capability,
+                version,
+            } => write!(
+                f,
+                "{} are not supported by maloja {}, they require {} or newer",
+                capability,
+                version,
+                capability.min_version()
+            ),
+        }
+    }
+}
+
+impl From<crate::json::Error> for RequestError {
+    fn from(error: crate::json::Error) -> Self {
+        RequestError::ServerError {
+            error_type: error._type,
+            desc: error.desc,
+        }
+    }
+}
+
+impl std::error::Error for RequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::TransportError(error) => Some(error),
+            RequestError::DecodeError { error, .. } => Some(error),
+            RequestError::EncodeError(error) => Some(error),
+            RequestError::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 /// An error building `MalojaCredentials`.
@@ -14,4 +138,140 @@ pub enum MalojaCredentialsBuilderError {
     MissingIP,
     /// The builder was missing a port.
     MissingPort,
+    /// The `reqwest::Client` for a [crate::client::MalojaClient] could not be created.
+    ClientError(reqwest::Error),
+}
+
+impl fmt::Display for MalojaCredentialsBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MalojaCredentialsBuilderError::MissingIP => write!(f, "no IP address was provided"),
+            MalojaCredentialsBuilderError::MissingPort => write!(f, "no port was provided"),
+            MalojaCredentialsBuilderError::ClientError(error) => {
+                write!(f, "could not create HTTP client: {}", error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MalojaCredentialsBuilderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MalojaCredentialsBuilderError::ClientError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// An error building a `ScrobbleSubmission`.
+#[derive(Debug)]
+pub enum ScrobbleSubmissionBuilderError {
+    /// The builder was missing a track title.
+    MissingTitle,
+    /// The builder was not given any artists.
+    MissingArtist,
+    /// The time the track was played is before 1970, which maloja can't store.
+    TimeBeforeEpoch,
+}
+
+impl fmt::Display for ScrobbleSubmissionBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScrobbleSubmissionBuilderError::MissingTitle => {
+                write!(f, "no track title was provided")
+            }
+            ScrobbleSubmissionBuilderError::MissingArtist => write!(f, "no artists were provided"),
+            ScrobbleSubmissionBuilderError::TimeBeforeEpoch => {
+                write!(f, "the time the track was played is before 1970")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScrobbleSubmissionBuilderError {}
+
+/// An error reading or writing a `ScrobbleQueue`.
+#[derive(Debug)]
+pub enum QueueError {
+    /// The queue file could not be read or written.
+    IoError(std::io::Error),
+    /// A line in the queue file is not a valid queued scrobble.
+    CorruptEntry {
+        /// The line number, starting at 1.
+        line: usize,
+        /// Why the line could not be parsed.
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for QueueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueError::IoError(error) => write!(f, "could not access queue file: {}", error),
+            QueueError::CorruptEntry { line, error } => {
+                write!(f, "invalid queue entry on line {}: {}", line, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QueueError::IoError(error) => Some(error),
+            QueueError::CorruptEntry { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error parsing a `Range` from a string, containing the string which could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRangeError(pub String);
+
+impl fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\" is not a valid range", self.0)
+    }
+}
+
+impl std::error::Error for ParseRangeError {}
+
+/// An error submitting scrobbles in batches, along with what the batches before the failed one achieved.
+#[derive(Debug)]
+pub struct BatchError<T> {
+    /// What the batches submitted before the failure achieved, e.g. how many scrobbles they contained.
+    pub completed: T,
+    /// The index of the batch which failed, starting at 0. Later batches weren't sent.
+    pub batch: usize,
+    /// Why the batch failed.
+    pub error: RequestError,
+}
+
+impl<T> fmt::Display for BatchError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "batch {} failed: {}", self.batch, self.error)
+    }
+}
+
+impl<T: fmt::Debug> std::error::Error for BatchError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+// For errors which happen before any batch is sent.
+impl<T: Default> From<RequestError> for BatchError<T> {
+    fn from(error: RequestError) -> Self {
+        BatchError {
+            completed: T::default(),
+            batch: 0,
+            error,
+        }
+    }
+}
+
+impl<T> From<BatchError<T>> for RequestError {
+    fn from(error: BatchError<T>) -> Self {
+        error.error
+    }
 }
@@ -1,12 +1,13 @@
+use crate::client::MalojaClient;
 use crate::errors::RequestError;
-use crate::full_query_path;
-use crate::{get_client_async, handle_response, parse_headers, MalojaCredentials};
+use crate::{block_on, full_query_path, handle_response, require_field, MalojaCredentials};
 use crate::{
     json::*,
     range::{process_range, Range},
     types::*,
 };
 use chrono::prelude::*;
+use futures::{future, stream, Stream, StreamExt};
 use reqwest::Client;
 
 /// A scrobble: track and timestamp.
@@ -18,6 +19,326 @@ pub struct Scrobble {
     pub track: Track,
 }
 
+/// Which scrobbles to stream with [scrobble_stream], and how to fetch them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScrobbleQuery {
+    /// Optionally, an artist to view scrobbles of.
+    pub artist: Option<String>,
+    /// A time range.
+    pub range: Range,
+    /// How many scrobbles to fetch per request.
+    pub page_size: u64,
+    /// How many pages to fetch ahead of the one being read.
+    pub prefetch: usize,
+}
+
+impl Default for ScrobbleQuery {
+    fn default() -> Self {
+        ScrobbleQuery {
+            artist: None,
+            range: Range::AllTime,
+            page_size: 100,
+            prefetch: 1,
+        }
+    }
+}
+
+impl ScrobbleQuery {
+    /// Creates a query for all scrobbles within a time range.
+    pub fn new(range: Range) -> Self {
+        ScrobbleQuery {
+            range,
+            ..Default::default()
+        }
+    }
+}
+
+/// The order in which a [ScrobbleIter] walks through scrobbles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// From the most recent scrobble to the oldest.
+    #[default]
+    NewestFirst,
+    /// From the oldest scrobble to the most recent.
+    OldestFirst,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Cursor {
+    Start,
+    Page(u64),
+    Done,
+}
+
+/// A blocking iterator over scrobbles, which fetches a page at a time. See [scrobble_iter].
+#[derive(Debug)]
+pub struct ScrobbleIter {
+    client: MalojaClient,
+    query: ScrobbleQuery,
+    order: Order,
+    stop_at: Option<DateTime<Utc>>,
+    cursor: Cursor,
+    page: std::vec::IntoIter<Scrobble>,
+}
+
+impl ScrobbleIter {
+    fn new(client: MalojaClient, query: ScrobbleQuery) -> Self {
+        ScrobbleIter {
+            client,
+            query: ScrobbleQuery {
+                page_size: query.page_size.max(1),
+                ..query
+            },
+            order: Order::NewestFirst,
+            stop_at: None,
+            cursor: Cursor::Start,
+            page: Vec::new().into_iter(),
+        }
+    }
+
+    /// Sets the order to walk through scrobbles in. [Order::NewestFirst] is used by default.
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Stops the iterator at the first scrobble past `time`: older than it when walking
+    /// newest first, or newer than it when walking oldest first. No further pages are fetched.
+    pub fn stop_at(mut self, time: DateTime<Utc>) -> Self {
+        self.stop_at = Some(time);
+        self
+    }
+
+    fn is_past_stop(&self, scrobble: &Scrobble) -> bool {
+        match (self.stop_at, self.order) {
+            (None, _) => false,
+            (Some(stop_at), Order::NewestFirst) => scrobble.time < stop_at,
+            (Some(stop_at), Order::OldestFirst) => scrobble.time > stop_at,
+        }
+    }
+
+    // Pages are numbered from the most recent scrobbles, so walking oldest first
+    // starts from the last page, which is found by counting the scrobbles.
+    fn first_page(&self) -> Result<Option<u64>, RequestError> {
+        match self.order {
+            Order::NewestFirst => Ok(Some(0)),
+            Order::OldestFirst => {
+                let amount = self
+                    .client
+                    .numscrobbles(self.query.artist.clone(), self.query.range)?;
+                Ok(amount
+                    .checked_sub(1)
+                    .map(|last| last / self.query.page_size))
+            }
+        }
+    }
+
+    fn fetch(&mut self) -> Result<(), RequestError> {
+        let page_number = match self.cursor {
+            Cursor::Done => return Ok(()),
+            Cursor::Page(page_number) => page_number,
+            Cursor::Start => match self.first_page()? {
+                None => {
+                    self.cursor = Cursor::Done;
+                    return Ok(());
+                }
+                Some(page_number) => page_number,
+            },
+        };
+        let mut page = self.client.scrobbles(
+            self.query.artist.clone(),
+            self.query.range,
+            Some(page_number),
+            Some(self.query.page_size),
+        )?;
+        self.cursor = match self.order {
+            // A page with fewer scrobbles than were asked for is the last one.
+            Order::NewestFirst if (page.len() as u64) < self.query.page_size => Cursor::Done,
+            Order::NewestFirst => Cursor::Page(page_number + 1),
+            Order::OldestFirst => {
+                page.reverse();
+                page_number
+                    .checked_sub(1)
+                    .map_or(Cursor::Done, Cursor::Page)
+            }
+        };
+        self.page = page.into_iter();
+        Ok(())
+    }
+}
+
+impl Iterator for ScrobbleIter {
+    type Item = Result<Scrobble, RequestError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(scrobble) = self.page.next() {
+                if self.is_past_stop(&scrobble) {
+                    self.cursor = Cursor::Done;
+                    self.page = Vec::new().into_iter();
+                    return None;
+                }
+                return Some(Ok(scrobble));
+            }
+            if let Cursor::Done = self.cursor {
+                return None;
+            }
+            if let Err(error) = self.fetch() {
+                self.cursor = Cursor::Done;
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for ScrobbleIter {}
+
+impl Scrobble {
+    pub(crate) fn from_scrobblestrackres(
+        scrobble: ScrobblesTrackRes,
+    ) -> Result<Scrobble, RequestError> {
+        let time: DateTime<Utc> = i64::try_from(scrobble.time)
+            .ok()
+            .and_then(|time| DateTime::from_timestamp(time, 0))
+            .ok_or_else(|| {
+                RequestError::MalformedResponse(format!(
+                    "scrobble time {} is out of range",
+                    scrobble.time
+                ))
+            })?;
+        Ok(Scrobble {
+            time,
+            track: Track::from_trackres(scrobble.track, None),
+        })
+    }
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::scrobbles].
+    pub async fn scrobbles_async(
+        &self,
+        artist: Option<String>,
+        range: Range,
+        page_number: Option<u64>,
+        scrobbles_per_page: Option<u64>,
+    ) -> Result<Vec<Scrobble>, RequestError> {
+        let from_until_in = process_range(range)?;
+        let requestbody = ScrobblesReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+            artist,
+            page: page_number,
+            perpage: scrobbles_per_page,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/scrobbles")?)
+            .send()
+            .await;
+        match handle_response::<ScrobblesRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut scrobbles: Vec<Scrobble> = vec![];
+                for scrobble in require_field(response.list, "list")? {
+                    scrobbles.push(Scrobble::from_scrobblestrackres(scrobble)?);
+                }
+                Ok(scrobbles)
+            }
+        }
+    }
+
+    /// Returns a `Vec` of scrobbles within a given time range. See [scrobbles] for the arguments.
+    pub fn scrobbles(
+        &self,
+        artist: Option<String>,
+        range: Range,
+        page_number: Option<u64>,
+        scrobbles_per_page: Option<u64>,
+    ) -> Result<Vec<Scrobble>, RequestError> {
+        let client = self.clone();
+        block_on(async move {
+            client
+                .scrobbles_async(artist, range, page_number, scrobbles_per_page)
+                .await
+        })
+    }
+
+    /// Streams scrobbles, fetching pages as they are needed. See [scrobble_stream].
+    pub fn scrobble_stream(
+        &self,
+        query: ScrobbleQuery,
+    ) -> impl Stream<Item = Result<Scrobble, RequestError>> + Send + 'static {
+        let client = self.clone();
+        let page_size = query.page_size.max(1);
+        stream::iter(0..)
+            .map(move |page| {
+                let client = client.clone();
+                let artist = query.artist.clone();
+                async move {
+                    client
+                        .scrobbles_async(artist, query.range, Some(page), Some(page_size))
+                        .await
+                }
+            })
+            .buffered(query.prefetch.saturating_add(1))
+            // A page with fewer scrobbles than were asked for is the last one.
+            .scan(false, move |done, page| {
+                if *done {
+                    return future::ready(None);
+                }
+                let scrobbles = match page {
+                    Err(error) => {
+                        *done = true;
+                        vec![Err(error)]
+                    }
+                    Ok(page) => {
+                        *done = (page.len() as u64) < page_size;
+                        page.into_iter().map(Ok).collect()
+                    }
+                };
+                future::ready(Some(stream::iter(scrobbles)))
+            })
+            .flatten()
+    }
+
+    /// Walks through scrobbles, fetching pages as they are needed. See [scrobble_iter].
+    pub fn scrobble_iter(&self, query: ScrobbleQuery) -> ScrobbleIter {
+        ScrobbleIter::new(self.clone(), query)
+    }
+
+    /// See [MalojaClient::numscrobbles].
+    pub async fn numscrobbles_async(
+        &self,
+        artist: Option<String>,
+        range: Range,
+    ) -> Result<u64, RequestError> {
+        let from_until_in = process_range(range)?;
+        // numscrobbles uses the same exact documentation/query structure as scrobbles even though pages aren't relevant
+        let requestbody = ScrobblesReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+            artist,
+            page: None,
+            perpage: None,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/numscrobbles")?)
+            .send()
+            .await;
+        match handle_response::<NumscrobblesRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => require_field(response.amount, "amount"),
+        }
+    }
+
+    /// Provides a scrobble count, within a given time range, optionally only for a certain artist.
+    pub fn numscrobbles(&self, artist: Option<String>, range: Range) -> Result<u64, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.numscrobbles_async(artist, range).await })
+    }
+}
+
 /// See [scrobbles].
 pub async fn scrobbles_async(
     artist: Option<String>,
@@ -27,38 +348,9 @@ pub async fn scrobbles_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<Vec<Scrobble>, RequestError> {
-    let from_until_in = process_range(range);
-    let requestbody = ScrobblesReq {
-        from: from_until_in.0,
-        until: from_until_in.1,
-        _in: from_until_in.2,
-        artist,
-        page: page_number,
-        perpage: scrobbles_per_page,
-    };
-    let response = client
-        .get(full_query_path(
-            requestbody,
-            &(credentials.get_url() + "/apis/mlj_1/scrobbles"),
-        ))
-        .headers(parse_headers(credentials.headers))
-        .send()
-        .await;
-    match handle_response::<ScrobblesRes>(response).await {
-        Err(error) => Err(error),
-        Ok(response) => {
-            let mut scrobbles: Vec<Scrobble> = vec![];
-            for scrobble in response.list.unwrap() {
-                let dt: DateTime<Utc> =
-                    DateTime::from_timestamp(scrobble.time.try_into().unwrap(), 0).unwrap();
-                scrobbles.push(Scrobble {
-                    time: dt,
-                    track: Track::from_trackres(scrobble.track, None),
-                });
-            }
-            Ok(scrobbles)
-        }
-    }
+    MalojaClient::with_client(credentials, client)
+        .scrobbles_async(artist, range, page_number, scrobbles_per_page)
+        .await
 }
 
 /// Returns a `Vec` of scrobbles within a given time range.
@@ -73,7 +365,9 @@ pub async fn scrobbles_async(
 /// 
 /// # Examples
 /// 
-/// ```
+/// ```no_run
+/// # use mljcl::history;
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 /// let recent_scrobbles_vec = mljcl::history::scrobbles(None, mljcl::range::Range::AllTime, Some(0), Some(30), creds).unwrap();
 /// let recent_scrobbles: Vec<String> = recent_scrobbles_vec
 ///     .into_iter()
@@ -88,18 +382,74 @@ pub fn scrobbles(
     scrobbles_per_page: Option<u64>,
     credentials: MalojaCredentials,
 ) -> Result<Vec<Scrobble>, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        scrobbles_async(
-            artist,
-            range,
-            page_number,
-            scrobbles_per_page,
-            credentials,
-            client.unwrap(),
-        )
-        .await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .scrobbles(artist, range, page_number, scrobbles_per_page)
+}
+
+/// Streams scrobbles, most recent first, fetching them a page at a time as the stream is read.
+///
+/// Up to `query.prefetch` pages are requested ahead of the one being read. The stream ends
+/// after the last page, or right after the first error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use mljcl::history::ScrobbleQuery;
+/// use mljcl::range::Range;
+///
+/// # async fn example() {
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// # let client = mljcl::get_client_async(&creds).unwrap();
+/// let query = ScrobbleQuery {
+///     page_size: 500,
+///     ..ScrobbleQuery::new(Range::ThisYear)
+/// };
+/// let mut scrobbles = Box::pin(mljcl::history::scrobble_stream(query, creds, client));
+/// while let Some(scrobble) = scrobbles.next().await {
+///     let scrobble = scrobble.unwrap();
+///     println!("{} - {}", scrobble.track.artists.join(", "), scrobble.track.name);
+/// }
+/// # }
+/// ```
+pub fn scrobble_stream(
+    query: ScrobbleQuery,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> impl Stream<Item = Result<Scrobble, RequestError>> + Send + 'static {
+    MalojaClient::with_client(credentials, client).scrobble_stream(query)
+}
+
+/// Walks through scrobbles one at a time, blocking while each page is fetched.
+///
+/// Scrobbles are returned newest first, unless set otherwise with [ScrobbleIter::order].
+/// [ScrobbleIter::stop_at] ends the iterator at a certain time, so that only the pages
+/// needed are downloaded. `query.prefetch` is ignored. The iterator ends right after the first error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::history::ScrobbleQuery;
+/// use mljcl::range::Range;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let last_week = chrono::Utc::now() - chrono::Duration::weeks(1);
+/// let scrobbles = mljcl::history::scrobble_iter(ScrobbleQuery::new(Range::AllTime), creds)
+///     .unwrap()
+///     .stop_at(last_week);
+/// for scrobble in scrobbles {
+///     let scrobble = scrobble.unwrap();
+///     println!("{} - {}", scrobble.track.artists.join(", "), scrobble.track.name);
+/// }
+/// ```
+pub fn scrobble_iter(
+    query: ScrobbleQuery,
+    credentials: MalojaCredentials,
+) -> Result<ScrobbleIter, RequestError> {
+    Ok(MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .scrobble_iter(query))
 }
 
 /// See [numscrobbles].
@@ -109,31 +459,9 @@ pub async fn numscrobbles_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<u64, RequestError> {
-    let from_until_in = process_range(range);
-    // numscrobbles uses the same exact documentation/query structure as scrobbles even though pages aren't relevant
-    let requestbody = ScrobblesReq {
-        from: from_until_in.0,
-        until: from_until_in.1,
-        _in: from_until_in.2,
-        artist,
-        page: None,
-        perpage: None,
-    };
-    let response = client
-        .get(full_query_path(
-            requestbody,
-            &(credentials.get_url() + "/apis/mlj_1/numscrobbles"),
-        ))
-        .headers(parse_headers(credentials.headers))
-        .send()
-        .await;
-    match handle_response::<NumscrobblesRes>(response).await {
-        Err(error) => Err(error),
-        Ok(response) => match response.amount {
-            Some(amount) => Ok(amount),
-            None => Err(RequestError::ServerError(response.status)),
-        },
-    }
+    MalojaClient::with_client(credentials, client)
+        .numscrobbles_async(artist, range)
+        .await
 }
 
 /// Provides a scrobble count, within a given time range, optionally only for a certain artist.
@@ -142,8 +470,169 @@ pub fn numscrobbles(
     range: Range,
     credentials: MalojaCredentials,
 ) -> Result<u64, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        numscrobbles_async(artist, range, credentials, client.unwrap()).await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .numscrobbles(artist, range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    fn client(responses: Vec<(u16, &str)>) -> MalojaClient {
+        MalojaClient::new(serve(responses).0).unwrap()
+    }
+
+    #[test]
+    fn scrobbles_without_list_are_malformed() {
+        let client = client(vec![(200, r#"{"status":"ok"}"#)]);
+        assert!(matches!(
+            client.scrobbles(None, Range::AllTime, None, None),
+            Err(RequestError::MalformedResponse(message)) if message.contains("`list`")
+        ));
+    }
+
+    #[test]
+    fn scrobble_time_out_of_range_is_malformed() {
+        let client = client(vec![(
+            200,
+            r#"{"status":"ok","list":[{"time":18446744073709551615,"track":{"artists":["A"],"title":"T"}}]}"#,
+        )]);
+        assert!(matches!(
+            client.scrobbles(None, Range::AllTime, None, None),
+            Err(RequestError::MalformedResponse(message)) if message.contains("out of range")
+        ));
+    }
+
+    // A page of scrobbles played at each of `times`.
+    fn page(times: &[u64]) -> String {
+        let scrobbles: Vec<String> = times
+            .iter()
+            .map(|time| {
+                format!(
+                    r#"{{"time":{},"track":{{"artists":["A"],"title":"T"}}}}"#,
+                    time
+                )
+            })
+            .collect();
+        format!(r#"{{"status":"ok","list":[{}]}}"#, scrobbles.join(","))
+    }
+
+    fn times(scrobbles: &[Result<Scrobble, RequestError>]) -> Vec<Option<i64>> {
+        scrobbles
+            .iter()
+            .map(|scrobble| {
+                scrobble
+                    .as_ref()
+                    .ok()
+                    .map(|scrobble| scrobble.time.timestamp())
+            })
+            .collect()
+    }
+
+    fn stream_all(client: &MalojaClient, page_size: u64) -> Vec<Result<Scrobble, RequestError>> {
+        let stream = client.scrobble_stream(ScrobbleQuery {
+            page_size,
+            prefetch: 0,
+            ..Default::default()
+        });
+        block_on(async move { Ok::<_, RequestError>(stream.collect::<Vec<_>>().await) }).unwrap()
+    }
+
+    #[test]
+    fn stream_concatenates_pages_until_a_short_one() {
+        let (first, second, third) = (page(&[5, 4]), page(&[3, 2]), page(&[1]));
+        let (credentials, requests) = serve(vec![(200, &first), (200, &second), (200, &third)]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let scrobbles = stream_all(&client, 2);
+        assert_eq!(
+            times(&scrobbles),
+            [Some(5), Some(4), Some(3), Some(2), Some(1)]
+        );
+        for page in 0..3 {
+            let request = requests.recv().unwrap();
+            assert!(request.contains(&format!("page={}&perpage=2", page)));
+        }
+    }
+
+    #[test]
+    fn stream_stops_at_an_empty_page() {
+        let (first, empty) = (page(&[2, 1]), page(&[]));
+        let client = client(vec![(200, &first), (200, &empty)]);
+        assert_eq!(times(&stream_all(&client, 2)), [Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn stream_ends_with_an_error() {
+        let first = page(&[4, 3]);
+        let client = client(vec![(200, &first), (500, "Internal Server Error")]);
+        let scrobbles = stream_all(&client, 2);
+        assert_eq!(times(&scrobbles), [Some(4), Some(3), None]);
+        assert!(matches!(
+            scrobbles[2],
+            Err(RequestError::StatusError { status, .. }) if status.as_u16() == 500
+        ));
+    }
+
+    #[test]
+    fn numscrobbles_without_amount_is_malformed() {
+        let client = client(vec![(200, r#"{"status":"ok"}"#)]);
+        assert!(matches!(
+            client.numscrobbles(None, Range::AllTime),
+            Err(RequestError::MalformedResponse(message)) if message.contains("`amount`")
+        ));
+    }
+
+    fn iter(client: &MalojaClient) -> ScrobbleIter {
+        client.scrobble_iter(ScrobbleQuery {
+            page_size: 2,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn oldest_first_walks_pages_backwards() {
+        let pages = [page(&[1]), page(&[3, 2]), page(&[5, 4])];
+        let (credentials, requests) = serve(vec![
+            (200, r#"{"status":"ok","amount":5}"#),
+            (200, &pages[0]),
+            (200, &pages[1]),
+            (200, &pages[2]),
+        ]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let scrobbles: Vec<_> = iter(&client).order(Order::OldestFirst).collect();
+        assert_eq!(
+            times(&scrobbles),
+            [Some(1), Some(2), Some(3), Some(4), Some(5)]
+        );
+        let requests: Vec<String> = requests.iter().collect();
+        assert!(requests[0].starts_with("GET /apis/mlj_1/numscrobbles"));
+        for (request, page) in requests[1..].iter().zip([2, 1, 0]) {
+            assert!(request.contains(&format!("page={}&perpage=2", page)));
+        }
+    }
+
+    #[test]
+    fn stop_at_fetches_no_further_pages() {
+        let pages = [page(&[5, 4]), page(&[3, 2])];
+        let (credentials, requests) = serve(vec![(200, &pages[0]), (200, &pages[1])]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let stop_at = DateTime::from_timestamp(3, 0).unwrap();
+        let scrobbles: Vec<_> = iter(&client).stop_at(stop_at).collect();
+        assert_eq!(times(&scrobbles), [Some(5), Some(4), Some(3)]);
+        assert_eq!(requests.iter().count(), 2);
+    }
+
+    #[test]
+    fn iterator_ends_after_an_error() {
+        let first = page(&[2, 1]);
+        let client = client(vec![(200, &first), (500, "Internal Server Error")]);
+        let mut scrobbles = iter(&client);
+        assert_eq!(
+            times(&scrobbles.by_ref().take(3).collect::<Vec<_>>()),
+            [Some(2), Some(1), None]
+        );
+        assert!(scrobbles.next().is_none());
+    }
 }
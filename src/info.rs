@@ -0,0 +1,254 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::server::Capability;
+use crate::{block_on, handle_response, require_field, MalojaCredentials};
+use crate::{json::*, types::*};
+use reqwest::Client;
+
+fn medals(medals: Option<MedalsRes>) -> Medals {
+    medals.map(Medals::from_medalsres).unwrap_or_default()
+}
+
+fn certification(certification: Option<String>) -> Option<Certification> {
+    certification.and_then(|name| Certification::from_name(&name))
+}
+
+// maloja looks up whichever kind of entity the query names, so e.g. a track passed to
+// `artist_info` would be answered with something other than artist details.
+fn require_kind(entity: &Entity, kind: &'static str) -> Result<(), RequestError> {
+    match entity.kind() == kind {
+        true => Ok(()),
+        false => Err(RequestError::WrongEntityKind {
+            expected: kind,
+            found: entity.kind(),
+        }),
+    }
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::artist_info].
+    pub async fn artist_info_async(&self, artist: Entity) -> Result<ArtistInfo, RequestError> {
+        require_kind(&artist, "artist")?;
+        let response = self
+            .get("/apis/mlj_1/artistinfo")
+            .query(&artist.query_pairs())
+            .send()
+            .await;
+        match handle_response::<ArtistInfoRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => Ok(ArtistInfo {
+                artist: Artist {
+                    name: require_field(response.artist, "artist")?,
+                    id: require_field(response.id, "id")?.to_string(),
+                },
+                scrobbles: response.scrobbles.unwrap_or(0),
+                position: response.position,
+                associated: response.associated,
+                medals: medals(response.medals),
+                top_weeks: response.topweeks.unwrap_or(0),
+                credited_as: response.replace,
+            }),
+        }
+    }
+
+    /// Fetches details about an artist. See [artist_info].
+    pub fn artist_info(&self, artist: Entity) -> Result<ArtistInfo, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.artist_info_async(artist).await })
+    }
+
+    /// See [MalojaClient::track_info].
+    pub async fn track_info_async(&self, track: Entity) -> Result<TrackInfo, RequestError> {
+        require_kind(&track, "track")?;
+        let response = self
+            .get("/apis/mlj_1/trackinfo")
+            .query(&track.query_pairs())
+            .send()
+            .await;
+        match handle_response::<TrackInfoRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => Ok(TrackInfo {
+                track: Track::from_trackres(
+                    require_field(response.track, "track")?,
+                    response.id.map(|id| id.to_string()),
+                ),
+                scrobbles: response.scrobbles.unwrap_or(0),
+                position: response.position,
+                medals: medals(response.medals),
+                certification: certification(response.certification),
+                top_weeks: response.topweeks.unwrap_or(0),
+            }),
+        }
+    }
+
+    /// Fetches details about a track. See [track_info].
+    pub fn track_info(&self, track: Entity) -> Result<TrackInfo, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.track_info_async(track).await })
+    }
+
+    /// See [MalojaClient::album_info].
+    pub async fn album_info_async(&self, album: Entity) -> Result<AlbumInfo, RequestError> {
+        require_kind(&album, "album")?;
+        self.gated(Capability::Albums, async {
+            let response = self
+                .get("/apis/mlj_1/albuminfo")
+                .query(&album.query_pairs())
+                .send()
+                .await;
+            match handle_response::<AlbumInfoRes>(response).await {
+                Err(error) => Err(error),
+                Ok(response) => {
+                    let album = require_field(response.album, "album")?;
+                    Ok(AlbumInfo {
+                        album: Album {
+                            name: album.albumtitle,
+                            id: require_field(response.id, "id")?.to_string(),
+                            artists: album.artists,
+                        },
+                        scrobbles: response.scrobbles.unwrap_or(0),
+                        position: response.position,
+                        medals: medals(response.medals),
+                        certification: certification(response.certification),
+                        top_weeks: response.topweeks.unwrap_or(0),
+                    })
+                }
+            }
+        })
+        .await
+    }
+
+    /// Fetches details about an album. See [album_info].
+    pub fn album_info(&self, album: Entity) -> Result<AlbumInfo, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.album_info_async(album).await })
+    }
+}
+
+/// See [artist_info].
+pub async fn artist_info_async(
+    artist: Entity,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<ArtistInfo, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .artist_info_async(artist)
+        .await
+}
+
+/// Fetches details about an artist: their scrobbles, all-time rank, medals and associated artists.
+///
+/// # Arguments
+///
+/// * `artist` - The artist, either by name (`Entity::Artist`) or by ID (`Entity::ArtistId`).
+///   Any other kind of entity gives [RequestError::WrongEntityKind].
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::types::Entity;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let info = mljcl::info::artist_info(Entity::Artist("Some artist".to_string()), creds).unwrap();
+/// println!("{} has {} scrobbles and {} gold medals", info.artist.name, info.scrobbles, info.medals.gold.len());
+/// ```
+pub fn artist_info(
+    artist: Entity,
+    credentials: MalojaCredentials,
+) -> Result<ArtistInfo, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .artist_info(artist)
+}
+
+/// See [track_info].
+pub async fn track_info_async(
+    track: Entity,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<TrackInfo, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .track_info_async(track)
+        .await
+}
+
+/// Fetches details about a track, given either its title and artists (`Entity::Track`) or its ID (`Entity::TrackId`).
+/// Any other kind of entity gives [RequestError::WrongEntityKind].
+pub fn track_info(
+    track: Entity,
+    credentials: MalojaCredentials,
+) -> Result<TrackInfo, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .track_info(track)
+}
+
+/// See [album_info].
+pub async fn album_info_async(
+    album: Entity,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<AlbumInfo, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .album_info_async(album)
+        .await
+}
+
+/// Fetches details about an album, given either its title and artists (`Entity::Album`) or its ID (`Entity::AlbumId`).
+/// Any other kind of entity gives [RequestError::WrongEntityKind].
+pub fn album_info(
+    album: Entity,
+    credentials: MalojaCredentials,
+) -> Result<AlbumInfo, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .album_info(album)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    #[test]
+    fn mismatched_entities_are_invalid() {
+        // Nothing is served, so any request would fail with a different error.
+        let client = MalojaClient::new(serve(vec![]).0).unwrap();
+        let track = Entity::TrackId("1".to_string());
+        assert!(matches!(
+            client.artist_info(track.clone()),
+            Err(RequestError::WrongEntityKind {
+                expected: "artist",
+                found: "track"
+            })
+        ));
+        assert!(matches!(
+            client.track_info(Entity::Artist("Artist".to_string())),
+            Err(RequestError::WrongEntityKind { .. })
+        ));
+        assert!(matches!(
+            client.album_info(track),
+            Err(RequestError::WrongEntityKind { .. })
+        ));
+    }
+
+    #[test]
+    fn artist_info_by_id() {
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{"artist":"Artist","id":4,"scrobbles":12,"position":3,"associated":[],"topweeks":1}"#,
+        )]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let info = client
+            .artist_info(Entity::ArtistId("4".to_string()))
+            .unwrap();
+        assert_eq!(info.artist.name, "Artist");
+        assert_eq!(info.artist.id, "4");
+        assert_eq!(info.scrobbles, 12);
+        assert!(requests
+            .recv()
+            .unwrap()
+            .starts_with("GET /apis/mlj_1/artistinfo?artist_id=4 "));
+    }
+}
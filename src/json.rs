@@ -24,18 +24,18 @@ pub struct ScrobbleReq {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub artist: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub artists: Option<String>,
+    pub artists: Option<Vec<String>>,
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub album: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub albumartists: Option<String>,
+    pub albumartists: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub length: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub time: Option<u64>,
+    pub time: Option<i64>,
     pub key: String,
 }
 
@@ -203,3 +203,453 @@ pub struct NumscrobblesRes {
 }
 
 impl_malojaresponse!(NumscrobblesRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct PulseReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "in")]
+    pub _in: Option<String>,
+    pub step: String,
+    pub stepn: u32,
+}
+
+// How maloja describes a time range in its responses
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct RangeRes {
+    pub fromstring: Option<String>,
+    pub tostring: Option<String>,
+    pub fromstamp: Option<i64>,
+    pub tostamp: Option<i64>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PulseEntryRes {
+    pub range: RangeRes,
+    pub scrobbles: u64,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PulseRes {
+    pub status: String,
+    pub list: Option<Vec<PulseEntryRes>>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(PulseRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct PerformanceReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "in")]
+    pub _in: Option<String>,
+    pub step: String,
+    pub stepn: u32,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PerformanceEntryRes {
+    pub range: RangeRes,
+    // `null` when the entity isn't in the charts for this period
+    pub rank: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct PerformanceRes {
+    pub status: String,
+    pub list: Option<Vec<PerformanceEntryRes>>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(PerformanceRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TopReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "in")]
+    pub _in: Option<String>,
+    pub step: String,
+    pub stepn: u32,
+}
+
+// The top entity is `null` for periods without any scrobbles
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopArtistRes {
+    pub range: RangeRes,
+    pub artist: Option<String>,
+    pub artist_id: Option<u64>,
+    pub scrobbles: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopArtistsRes {
+    pub status: String,
+    pub list: Option<Vec<TopArtistRes>>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(TopArtistsRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopTrackRes {
+    pub range: RangeRes,
+    pub track: Option<TrackRes>,
+    pub track_id: Option<u64>,
+    pub scrobbles: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopTracksRes {
+    pub status: String,
+    pub list: Option<Vec<TopTrackRes>>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(TopTracksRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopAlbumRes {
+    pub range: RangeRes,
+    pub album: Option<AlbumRes>,
+    pub album_id: Option<u64>,
+    pub scrobbles: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TopAlbumsRes {
+    pub status: String,
+    pub list: Option<Vec<TopAlbumRes>>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(TopAlbumsRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct MedalsRes {
+    #[serde(default)]
+    pub gold: Vec<String>,
+    #[serde(default)]
+    pub silver: Vec<String>,
+    #[serde(default)]
+    pub bronze: Vec<String>,
+}
+
+// The info endpoints don't include a status, only the entity's details or an error
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct ArtistInfoRes {
+    pub artist: Option<String>,
+    pub id: Option<u64>,
+    pub scrobbles: Option<u64>,
+    pub position: Option<u64>,
+    #[serde(default)]
+    pub associated: Vec<String>,
+    pub medals: Option<MedalsRes>,
+    pub topweeks: Option<u64>,
+    // Set when the artist only appears in the charts credited as another artist
+    pub replace: Option<String>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(ArtistInfoRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TrackInfoRes {
+    pub track: Option<TrackRes>,
+    pub id: Option<u64>,
+    pub scrobbles: Option<u64>,
+    pub position: Option<u64>,
+    pub medals: Option<MedalsRes>,
+    pub certification: Option<String>,
+    pub topweeks: Option<u64>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(TrackInfoRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct AlbumInfoRes {
+    pub album: Option<AlbumRes>,
+    pub id: Option<u64>,
+    pub scrobbles: Option<u64>,
+    pub position: Option<u64>,
+    pub medals: Option<MedalsRes>,
+    pub certification: Option<String>,
+    pub topweeks: Option<u64>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(AlbumInfoRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct SearchReq {
+    pub query: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+}
+
+// Search results link to the entity's page and image, which is where their IDs can be found
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SearchArtistRes {
+    pub artist: String,
+    pub artist_id: Option<u64>,
+    pub link: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SearchTrackRes {
+    pub track: TrackRes,
+    pub track_id: Option<u64>,
+    pub link: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SearchAlbumRes {
+    pub album: AlbumRes,
+    pub album_id: Option<u64>,
+    pub link: Option<String>,
+    pub image: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SearchRes {
+    #[serde(default)]
+    pub artists: Vec<SearchArtistRes>,
+    #[serde(default)]
+    pub tracks: Vec<SearchTrackRes>,
+    #[serde(default)]
+    pub albums: Vec<SearchAlbumRes>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(SearchRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct DbStatusRes {
+    pub healthy: Option<bool>,
+    pub rebuildinprogress: Option<bool>,
+    pub complete: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct ServerInfoRes {
+    pub name: Option<String>,
+    // A list of version components, as strings or numbers depending on the maloja version
+    pub version: Option<serde_json::Value>,
+    pub versionstring: Option<String>,
+    pub db_status: Option<DbStatusRes>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(ServerInfoRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TestReq {
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TestRes {
+    pub status: String,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(TestRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ScrobbleTimestampReq {
+    pub timestamp: i64,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct StatusRes {
+    pub status: String,
+    pub desc: Option<String>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(StatusRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ReparseScrobbleRes {
+    pub status: String,
+    pub desc: Option<String>,
+    pub scrobble: Option<ScrobblesTrackRes>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(ReparseScrobbleRes);
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct EditArtistReq {
+    pub id: u64,
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct EditTrackReq {
+    pub id: u64,
+    pub title: String,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct EditAlbumReq {
+    pub id: u64,
+    pub albumtitle: String,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct MergeReq {
+    // `None` is only used to remove tracks from their album
+    pub target_id: Option<u64>,
+    pub source_ids: Vec<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove: Option<bool>,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct AddPictureReq {
+    pub b64: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_id: Option<String>,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct AddPictureRes {
+    pub status: String,
+    pub url: Option<String>,
+    pub error: Option<Error>,
+}
+
+impl_malojaresponse!(AddPictureRes);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ListenType {
+    Single,
+    Import,
+    PlayingNow,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ListenAdditionalInfoReq {
+    pub artist_names: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_artist_names: Option<Vec<String>>,
+    // maloja reads the track length in seconds from `duration`, ListenBrainz itself uses `duration_ms`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u128>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ListenTrackMetadataReq {
+    pub artist_name: String,
+    pub track_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release_name: Option<String>,
+    pub additional_info: ListenAdditionalInfoReq,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ListenReq {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub listened_at: Option<i64>,
+    pub track_metadata: ListenTrackMetadataReq,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct SubmitListensReq {
+    pub listen_type: ListenType,
+    pub payload: Vec<ListenReq>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct SubmitListensRes {
+    pub status: String,
+}
+
+// ListenBrainz reports errors through the HTTP status, with `error` as a plain string.
+impl MalojaResponse for SubmitListensRes {
+    fn get_error(&self) -> Option<Error> {
+        None
+    }
+}
+
+// Audioscrobbler errors are a code and a message next to each other, rather than an object.
+macro_rules! impl_audioscrobblerresponse {
+    ($($names:ident)+) => {
+        $(impl MalojaResponse for $names {
+            fn get_error(&self) -> Option<Error> {
+                self.error.map(|code| Error {
+                    _type: code.to_string(),
+                    desc: self.message.clone().unwrap_or_default(),
+                })
+            }
+        })+
+   }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct AudioscrobblerSessionRes {
+    pub name: Option<String>,
+    pub key: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct GetMobileSessionRes {
+    pub session: Option<AudioscrobblerSessionRes>,
+    pub error: Option<u64>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TrackScrobbleAttrRes {
+    // Numbers on Last.fm, but strings on some compatible servers
+    pub accepted: Option<serde_json::Value>,
+    pub ignored: Option<serde_json::Value>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TrackScrobbleListRes {
+    #[serde(rename = "@attr")]
+    pub attr: Option<TrackScrobbleAttrRes>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct TrackScrobbleRes {
+    pub scrobbles: Option<TrackScrobbleListRes>,
+    pub error: Option<u64>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct UpdateNowPlayingRes {
+    pub nowplaying: Option<serde_json::Value>,
+    pub error: Option<u64>,
+    pub message: Option<String>,
+}
+
+impl_audioscrobblerresponse!(GetMobileSessionRes TrackScrobbleRes UpdateNowPlayingRes);
@@ -24,23 +24,56 @@
 //!
 //! You can then, for example, get today's scrobbles for a certain artist:
 //!
-//! ```
+//! ```no_run
+//! # use mljcl::range::Range;
+//! # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 //! let artist = "Some artist";
 //! println!("Today's scrobbles for {}: {}", artist, mljcl::history::numscrobbles(
 //!     Some(artist.to_string()),
-//!     Range::In("today".to_string()),
+//!     Range::Today,
 //!     creds
 //! ).unwrap());
 //! ```
 //! 
 //! All async variants of functions require a `reqwest::Client`, which [get_client_async] provides.
+//! Blocking variants run on a background runtime shared by all of mljcl, so they can be
+//! called from any thread, including from inside another async runtime.
+//!
+//! If you make many requests to the same server, build a [client::MalojaClient] instead.
+//! It holds on to the credentials and a connection pool, and exposes every endpoint as a method:
+//!
+//! ```no_run
+//! let client = mljcl::credentials::MalojaCredentialsBuilder::new()
+//!     .ip("127.0.0.1".into())
+//!     .port(42010)
+//!     .api_key("my api key".into())
+//!     .build_client()
+//!     .unwrap();
+//! client.scrobble("Some track".into(), "Some artist".into()).unwrap();
+//! ```
 
+/// Submitting scrobbles through the Audioscrobbler 2.0 (Last.fm compatible) protocol.
+pub mod audioscrobbler;
+/// Submitting scrobbles through the legacy Audioscrobbler 1.2 protocol.
+pub mod audioscrobbler_legacy;
+/// A reusable client for a single maloja server.
+pub mod client;
 /// Creating and using server API credentials, as well as the IP and port of the server.
 pub mod credentials;
 /// Serverside and client side errors when making requests.
 pub mod errors;
 /// Raw JSON objects used for communicating between server and client.
 pub mod json;
+/// Submitting scrobbles through maloja's ListenBrainz-compatible API.
+pub mod listenbrainz;
+/// A persistent queue for scrobbles made while the server is unreachable.
+pub mod queue;
+/// Information about a maloja server, and the features its version supports.
+pub mod server;
+/// Scrobbles to submit, along with their album, artists, and play time.
+pub mod submission;
+#[cfg(test)]
+mod testing;
 
 /// Types for artists, tracks, and albums.
 #[cfg(feature = "full")]
@@ -58,60 +91,146 @@ pub mod history;
 #[cfg(feature = "full")]
 pub mod range;
 
-/// Requesting album and artist art as images.
+/// Requesting and uploading album and artist art.
 #[cfg(feature = "full")]
 pub mod art;
 
+/// Scrobble counts over time.
+#[cfg(feature = "full")]
+pub mod pulse;
+
+/// Chart ranks of artists, tracks, and albums over time.
+#[cfg(feature = "full")]
+pub mod performance;
+
+/// The most listened artist, track, or album of each period in a time range.
+#[cfg(feature = "full")]
+pub mod top;
+
+/// Details about artists, tracks, and albums.
+#[cfg(feature = "full")]
+pub mod info;
+
+/// Searching for artists, tracks, and albums by name.
+#[cfg(feature = "full")]
+pub mod search;
+
+/// Correcting the server's data: deleting and reparsing scrobbles, and editing and merging
+/// artists, tracks, and albums.
+#[cfg(feature = "full")]
+pub mod manage;
+
+/// Announcing the track which is currently playing.
+#[cfg(feature = "full")]
+pub mod now_playing;
+
+/// Mirroring scrobble history incrementally, picking up where the last sync stopped.
+#[cfg(feature = "full")]
+pub mod sync;
+
+use crate::client::{MalojaClient, ScrobbleProtocol};
 use crate::credentials::MalojaCredentials;
 use crate::errors::RequestError;
-use crate::json::{ScrobbleReq, ScrobbleRes};
+use crate::json::{ScrobbleRes, ScrobbleResTrack, TestReq, TestRes};
+use crate::submission::ScrobbleSubmission;
 use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
+use std::sync::OnceLock;
 
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
-    Client,
+    Client, StatusCode,
 };
+use tokio::runtime::Runtime;
 
-fn full_query_path<T: for<'de> serde::Serialize>(query: T, path: &str) -> String {
-    let qs = serde_qs::to_string(&query).unwrap();
+fn full_query_path<T: for<'de> serde::Serialize>(
+    query: T,
+    path: &str,
+) -> Result<String, RequestError> {
+    let qs = serde_qs::to_string(&query).map_err(RequestError::EncodeError)?;
     match qs.is_empty() {
-        true => path.to_string(),
-        false => path.to_string() + "?" + &qs,
+        true => Ok(path.to_string()),
+        false => Ok(path.to_string() + "?" + &qs),
     }
 }
 
+fn require_field<T>(field: Option<T>, name: &str) -> Result<T, RequestError> {
+    field.ok_or_else(|| {
+        RequestError::MalformedResponse(format!("response did not include `{}`", name))
+    })
+}
+
 fn parse_headers(maybe_headers: Option<HashMap<String, String>>) -> HeaderMap {
     let mut map = HeaderMap::new();
     if let Some(headers) = maybe_headers {
-        for key in headers.keys() {
-            let header_key = HeaderName::from_str(key);
-            let header_value = HeaderValue::from_str(headers.get(key).unwrap());
-            if header_key.is_err() || header_value.is_err() {
-                continue;
+        for (key, value) in headers.iter() {
+            if let (Ok(header_key), Ok(header_value)) =
+                (HeaderName::from_str(key), HeaderValue::from_str(value))
+            {
+                map.insert(header_key, header_value);
             }
-            map.insert(header_key.unwrap(), header_value.unwrap());
         }
     }
     map
 }
 
-async fn handle_response<T: crate::json::MalojaResponse + for<'de> serde::Deserialize<'de>>(
-    response: Result<reqwest::Response, reqwest::Error>,
+// maloja usually reports errors as `{"error": {"type": ..., "desc": ...}}`,
+// but some endpoints only give a description: `{"error": "..."}`, and
+// Audioscrobbler gives a code and a message: `{"error": 9, "message": "..."}`.
+fn parse_error(body: &str) -> Option<crate::json::Error> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    match value.get("error")? {
+        serde_json::Value::String(desc) => Some(crate::json::Error {
+            _type: String::new(),
+            desc: desc.clone(),
+        }),
+        serde_json::Value::Number(code) => Some(crate::json::Error {
+            _type: code.to_string(),
+            desc: value
+                .get("message")
+                .and_then(|message| message.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        }),
+        error => serde_json::from_value(error.clone()).ok(),
+    }
+}
+
+fn parse_response<T: crate::json::MalojaResponse + for<'de> serde::Deserialize<'de>>(
+    status: StatusCode,
+    body: String,
 ) -> Result<T, RequestError> {
-    if response.is_err() {
-        return Err(RequestError::ReqwestError(response.err().unwrap()));
+    if !status.is_success() {
+        return Err(match (status, parse_error(&body)) {
+            (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN, error) => {
+                RequestError::Unauthorized(error.map_or_else(|| status.to_string(), |e| e.desc))
+            }
+            (_, Some(error)) => RequestError::from(error),
+            (status, None) => RequestError::StatusError { status, body },
+        });
     }
-    let response = response.unwrap();
-    match response.json::<T>().await {
-        Err(error) => Err(RequestError::ReqwestError(error)),
+    match serde_json::from_str::<T>(&body) {
+        Err(error) => Err(RequestError::DecodeError { error, body }),
         Ok(parsed_response) => match parsed_response.get_error() {
             None => Ok(parsed_response),
-            Some(error) => Err(RequestError::ServerError(error.desc)),
+            Some(error) => Err(RequestError::from(error)),
         },
     }
 }
 
+async fn handle_response<T: crate::json::MalojaResponse + for<'de> serde::Deserialize<'de>>(
+    response: Result<reqwest::Response, reqwest::Error>,
+) -> Result<T, RequestError> {
+    let response = response.map_err(RequestError::TransportError)?;
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(RequestError::TransportError)?;
+    parse_response(status, body)
+}
+
 /// Provides a `reqwest::Client` given `MalojaCredentials`. It is recommended to use
 /// this function specifically as it takes into account whether HTTPS certificate
 /// verification should be skipped.
@@ -122,6 +241,7 @@ async fn handle_response<T: crate::json::MalojaResponse + for<'de> serde::Deseri
 /// # Examples
 /// 
 /// ```
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
 /// let client = mljcl::get_client_async(&creds);
 /// ```
 pub fn get_client_async(
@@ -132,6 +252,200 @@ pub fn get_client_async(
         .build()
 }
 
+fn blocking_runtime() -> Result<&'static Runtime, RequestError> {
+    static RUNTIME: OnceLock<Result<Runtime, String>> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .thread_name("mljcl-blocking")
+                .enable_all()
+                .build()
+                .map_err(|error| error.to_string())
+        })
+        .as_ref()
+        .map_err(|error| RequestError::RuntimeError(error.clone()))
+}
+
+// Blocking functions run their future on a runtime shared by the whole process, instead of
+// creating one per call. Since the future is spawned rather than driven by `Runtime::block_on`,
+// this is also safe to call from inside another runtime; only the calling thread is blocked.
+fn block_on<F, T, E>(future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: From<RequestError> + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+    blocking_runtime()?.spawn(async move {
+        let _ = sender.send(future.await);
+    });
+    // The sender is only dropped without sending if the task panicked.
+    receiver.recv().unwrap_or_else(|_| {
+        Err(RequestError::RuntimeError("the request's task panicked".to_string()).into())
+    })
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::validate_key].
+    pub async fn validate_key_async(&self) -> Result<(), RequestError> {
+        let requestbody = TestReq {
+            key: self.api_key()?,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/test")?)
+            .send()
+            .await;
+        let response = handle_response::<TestRes>(response).await?;
+        match response.status.as_str() {
+            "ok" => Ok(()),
+            _ => Err(RequestError::Unauthorized(response.status)),
+        }
+    }
+
+    /// Checks whether the server accepts this client's API key. See [validate_key].
+    pub fn validate_key(&self) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.validate_key_async().await })
+    }
+
+    /// See [MalojaClient::submit_scrobble].
+    pub async fn submit_scrobble_async(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<ScrobbleRes, RequestError> {
+        if self.protocol() == ScrobbleProtocol::ListenBrainz {
+            // ListenBrainz only confirms that the listen was received, so the response
+            // is filled in from the submission, as documented on `submit_scrobble`.
+            let track = ScrobbleResTrack {
+                artists: submission.artists.clone(),
+                title: submission.title.clone(),
+            };
+            self.submit_listen_async(submission).await?;
+            return Ok(ScrobbleRes {
+                status: "success".to_string(),
+                desc: None,
+                track: Some(track),
+                error: None,
+            });
+        }
+        let scrobblebody = submission.into_scrobblereq(self.api_key()?);
+        let response = self
+            .post("/apis/mlj_1/newscrobble")
+            .json(&scrobblebody)
+            .send()
+            .await;
+        handle_response::<ScrobbleRes>(response).await
+    }
+
+    /// Submits a scrobble with all of its metadata, through the API set by [MalojaClient::with_protocol].
+    /// See [submit_scrobble].
+    ///
+    /// Through [ScrobbleProtocol::ListenBrainz], the submission's `duration` isn't sent, and the
+    /// result is built from the submission rather than maloja's response: its status is always
+    /// `success`, and its track is the submitted title and artists.
+    pub fn submit_scrobble(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<ScrobbleRes, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.submit_scrobble_async(submission).await })
+    }
+
+    /// See [MalojaClient::scrobble].
+    pub async fn scrobble_async(
+        &self,
+        title: String,
+        artist: String,
+    ) -> Result<ScrobbleRes, RequestError> {
+        self.submit_scrobble_async(ScrobbleSubmission {
+            title,
+            artists: vec![artist],
+            album: None,
+            album_artists: None,
+            time: None,
+            duration: None,
+            length: None,
+        })
+        .await
+    }
+
+    /// Submits a scrobble.
+    pub fn scrobble(&self, title: String, artist: String) -> Result<ScrobbleRes, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.scrobble_async(title, artist).await })
+    }
+}
+
+/// See [validate_key].
+pub async fn validate_key_async(
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .validate_key_async()
+        .await
+}
+
+/// Checks whether the server accepts the API key in the given credentials, without submitting anything.
+///
+/// Returns [RequestError::MissingApiKey] if the credentials have no API key,
+/// [RequestError::Unauthorized] if the server rejects it, and
+/// [RequestError::TransportError] if the server can't be reached.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// match mljcl::validate_key(creds) {
+///     Ok(()) => println!("Key accepted"),
+///     Err(mljcl::errors::RequestError::Unauthorized(_)) => println!("Key rejected"),
+///     Err(error) => println!("Couldn't check the key: {}", error),
+/// }
+/// ```
+pub fn validate_key(credentials: MalojaCredentials) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .validate_key()
+}
+
+/// See [submit_scrobble].
+pub async fn submit_scrobble_async(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<ScrobbleRes, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .submit_scrobble_async(submission)
+        .await
+}
+
+/// Submits a scrobble with all of its metadata, such as its album or when it was played.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// let submission = mljcl::submission::ScrobbleSubmission::builder()
+///     .title("Some track".into())
+///     .artist("Some artist".into())
+///     .artist("Another artist".into())
+///     .album("Some album".into())
+///     .time(chrono::Utc::now() - chrono::Duration::hours(2))
+///     .length(std::time::Duration::from_secs(215))
+///     .build()
+///     .unwrap();
+/// mljcl::submit_scrobble(submission, creds).unwrap();
+/// ```
+pub fn submit_scrobble(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+) -> Result<ScrobbleRes, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .submit_scrobble(submission)
+}
+
 /// See [scrobble].
 pub async fn scrobble_async(
     title: String,
@@ -139,24 +453,9 @@ pub async fn scrobble_async(
     credentials: MalojaCredentials,
     client: Client,
 ) -> Result<ScrobbleRes, RequestError> {
-    let scrobblebody = ScrobbleReq {
-        artist: Some(artist),
-        artists: None,
-        title,
-        album: None,
-        albumartists: None,
-        duration: None,
-        length: None,
-        time: None,
-        key: credentials.api_key.as_ref().unwrap().to_string(),
-    };
-    let response = client
-        .post(credentials.get_url() + "/apis/mlj_1/newscrobble")
-        .headers(parse_headers(credentials.headers))
-        .json(&scrobblebody)
-        .send()
-        .await;
-    handle_response::<ScrobbleRes>(response).await
+    MalojaClient::with_client(credentials, client)
+        .scrobble_async(title, artist)
+        .await
 }
 
 /// Submits a scrobble.
@@ -165,8 +464,140 @@ pub fn scrobble(
     artist: String,
     credentials: MalojaCredentials,
 ) -> Result<ScrobbleRes, RequestError> {
-    tokio::runtime::Runtime::new().unwrap().block_on(async {
-        let client = get_client_async(&credentials);
-        scrobble_async(title, artist, credentials, client.unwrap()).await
-    })
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .scrobble(title, artist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::ArtistChartRes;
+
+    fn parse(status: u16, body: &str) -> Result<ArtistChartRes, RequestError> {
+        parse_response(StatusCode::from_u16(status).unwrap(), body.to_string())
+    }
+
+    #[test]
+    fn missing_list_is_malformed() {
+        let response = parse(200, r#"{"status":"ok"}"#).unwrap();
+        assert!(matches!(
+            require_field(response.list, "list"),
+            Err(RequestError::MalformedResponse(message)) if message.contains("`list`")
+        ));
+    }
+
+    #[test]
+    fn non_json_body_is_a_decode_error() {
+        assert!(matches!(
+            parse(200, "<html>Login</html>"),
+            Err(RequestError::DecodeError { body, .. }) if body == "<html>Login</html>"
+        ));
+    }
+
+    #[test]
+    fn string_error_is_a_server_error() {
+        assert!(matches!(
+            parse(400, r#"{"code":400,"error":"Invalid token"}"#),
+            Err(RequestError::ServerError { error_type, desc })
+                if error_type.is_empty() && desc == "Invalid token"
+        ));
+    }
+
+    #[test]
+    fn object_error_is_a_server_error() {
+        let body = r#"{"status":"error","error":{"type":"entity_does_not_exist","desc":"No such artist"}}"#;
+        for status in [200, 404, 422] {
+            assert!(matches!(
+                parse(status, body),
+                Err(RequestError::ServerError { error_type, desc })
+                    if error_type == "entity_does_not_exist" && desc == "No such artist"
+            ));
+        }
+    }
+
+    #[test]
+    fn numeric_error_is_a_server_error() {
+        assert!(matches!(
+            parse(400, r#"{"error":9,"message":"Invalid session key"}"#),
+            Err(RequestError::ServerError { error_type, desc })
+                if error_type == "9" && desc == "Invalid session key"
+        ));
+    }
+
+    #[test]
+    fn unauthorized_and_forbidden() {
+        assert!(matches!(
+            parse(401, r#"{"status":"failure","error":{"type":"authentication_fail","desc":"Invalid key"}}"#),
+            Err(RequestError::Unauthorized(desc)) if desc == "Invalid key"
+        ));
+        assert!(matches!(
+            parse(403, "Forbidden"),
+            Err(RequestError::Unauthorized(desc)) if desc.starts_with("403")
+        ));
+    }
+
+    #[test]
+    fn server_error_page_is_a_status_error() {
+        assert!(matches!(
+            parse(502, "<html><body>Bad Gateway</body></html>"),
+            Err(RequestError::StatusError { status, body })
+                if status == StatusCode::BAD_GATEWAY && body.contains("Bad Gateway")
+        ));
+    }
+
+    #[test]
+    fn listenbrainz_result_is_built_from_the_submission() {
+        let (credentials, requests) = testing::serve(vec![(200, r#"{"status":"ok"}"#)]);
+        let client = MalojaClient::new(credentials)
+            .unwrap()
+            .with_protocol(ScrobbleProtocol::ListenBrainz);
+        let submission = ScrobbleSubmission::builder()
+            .title("Track".to_string())
+            .artists(vec!["A".to_string(), "B".to_string()])
+            .build()
+            .unwrap();
+        let response = client.submit_scrobble(submission).unwrap();
+        assert_eq!(response.status, "success");
+        assert_eq!(
+            response.track,
+            Some(ScrobbleResTrack {
+                artists: vec!["A".to_string(), "B".to_string()],
+                title: "Track".to_string(),
+            })
+        );
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /apis/listenbrainz/1/submit-listens "));
+        assert!(request.contains("authorization: Token key"));
+    }
+
+    #[test]
+    fn validate_key_checks_the_key() {
+        let (credentials, requests) = testing::serve(vec![
+            (200, r#"{"status":"ok"}"#),
+            (403, r#"{"status":"error","error":"Wrong API key"}"#),
+        ]);
+        let client = MalojaClient::new(credentials.clone()).unwrap();
+        client.validate_key().unwrap();
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("GET /apis/mlj_1/test?key=key "));
+        assert!(matches!(
+            client.validate_key(),
+            Err(RequestError::Unauthorized(desc)) if desc == "Wrong API key"
+        ));
+        let without_key = MalojaCredentials {
+            api_key: None,
+            ..credentials
+        };
+        assert!(matches!(
+            MalojaClient::new(without_key).unwrap().validate_key(),
+            Err(RequestError::MissingApiKey)
+        ));
+    }
+
+    #[test]
+    fn successful_response_parses() {
+        let response = parse(200, r#"{"status":"ok","list":[]}"#).unwrap();
+        assert_eq!(response.list, Some(vec![]));
+    }
 }
@@ -0,0 +1,161 @@
+use crate::client::MalojaClient;
+use crate::credentials::MalojaCredentials;
+use crate::errors::RequestError;
+use crate::json::{ListenType, SubmitListensReq, SubmitListensRes};
+use crate::submission::ScrobbleSubmission;
+use crate::{block_on, handle_response};
+use reqwest::header::AUTHORIZATION;
+use reqwest::Client;
+
+impl MalojaClient {
+    async fn submit_listens_async(
+        &self,
+        listen_type: ListenType,
+        submissions: Vec<ScrobbleSubmission>,
+    ) -> Result<(), RequestError> {
+        let requestbody = SubmitListensReq {
+            listen_type,
+            payload: submissions
+                .into_iter()
+                .map(|submission| submission.into_listenreq(listen_type))
+                .collect(),
+        };
+        let response = self
+            .post("/apis/listenbrainz/1/submit-listens")
+            .header(AUTHORIZATION, format!("Token {}", self.api_key()?))
+            .json(&requestbody)
+            .send()
+            .await;
+        handle_response::<SubmitListensRes>(response).await?;
+        Ok(())
+    }
+
+    /// See [MalojaClient::submit_listen].
+    pub async fn submit_listen_async(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<(), RequestError> {
+        self.submit_listens_async(ListenType::Single, vec![submission])
+            .await
+    }
+
+    /// Submits a single scrobble through the ListenBrainz API. See [submit_listen].
+    pub fn submit_listen(&self, submission: ScrobbleSubmission) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.submit_listen_async(submission).await })
+    }
+
+    /// See [MalojaClient::import_listens].
+    pub async fn import_listens_async(
+        &self,
+        submissions: Vec<ScrobbleSubmission>,
+    ) -> Result<(), RequestError> {
+        self.submit_listens_async(ListenType::Import, submissions)
+            .await
+    }
+
+    /// Submits past scrobbles in bulk through the ListenBrainz API. See [import_listens].
+    pub fn import_listens(&self, submissions: Vec<ScrobbleSubmission>) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.import_listens_async(submissions).await })
+    }
+
+    /// See [MalojaClient::playing_now_listen].
+    pub async fn playing_now_listen_async(
+        &self,
+        submission: ScrobbleSubmission,
+    ) -> Result<(), RequestError> {
+        self.submit_listens_async(ListenType::PlayingNow, vec![submission])
+            .await
+    }
+
+    /// Reports the track currently playing through the ListenBrainz API. See [playing_now_listen].
+    pub fn playing_now_listen(&self, submission: ScrobbleSubmission) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.playing_now_listen_async(submission).await })
+    }
+}
+
+/// See [submit_listen].
+pub async fn submit_listen_async(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .submit_listen_async(submission)
+        .await
+}
+
+/// Submits a single scrobble through maloja's ListenBrainz-compatible API, using the API key as the token.
+///
+/// This is the same as [crate::submit_scrobble], but for servers where only the ListenBrainz API
+/// is reachable. To send every scrobble of a [MalojaClient] this way, use
+/// [MalojaClient::with_protocol] with [crate::client::ScrobbleProtocol::ListenBrainz].
+///
+/// The submission's `duration` isn't sent, since ListenBrainz has no field for it. The track's
+/// `length` is sent as its duration.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// let submission = mljcl::submission::ScrobbleSubmission::builder()
+///     .title("Some track".into())
+///     .artist("Some artist".into())
+///     .build()
+///     .unwrap();
+/// mljcl::listenbrainz::submit_listen(submission, creds).unwrap();
+/// ```
+pub fn submit_listen(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .submit_listen(submission)
+}
+
+/// See [import_listens].
+pub async fn import_listens_async(
+    submissions: Vec<ScrobbleSubmission>,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .import_listens_async(submissions)
+        .await
+}
+
+/// Submits many past scrobbles in one request through maloja's ListenBrainz-compatible API.
+/// Scrobbles without a time are given the current time. See [submit_listen] for which fields are sent.
+pub fn import_listens(
+    submissions: Vec<ScrobbleSubmission>,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .import_listens(submissions)
+}
+
+/// See [playing_now_listen].
+pub async fn playing_now_listen_async(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .playing_now_listen_async(submission)
+        .await
+}
+
+/// Reports the track currently playing through maloja's ListenBrainz-compatible API.
+/// maloja accepts these but doesn't record them, so the submission's time is ignored.
+pub fn playing_now_listen(
+    submission: ScrobbleSubmission,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .playing_now_listen(submission)
+}
@@ -0,0 +1,751 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::history::Scrobble;
+use crate::server::Capability;
+use crate::{block_on, handle_response, MalojaCredentials};
+use crate::{json::*, types::*};
+use chrono::prelude::*;
+use reqwest::Client;
+
+/// The outcome of reparsing a scrobble.
+#[derive(Clone, Debug)]
+pub enum ReparseResult {
+    /// The scrobble's raw data was parsed into a different track, and the scrobble was updated.
+    Reparsed(Scrobble),
+    /// The scrobble was already up to date, or has no raw data to reparse.
+    Unchanged,
+}
+
+impl MalojaClient {
+    async fn manage_async<T: serde::Serialize>(
+        &self,
+        path: &str,
+        requestbody: &T,
+    ) -> Result<(), RequestError> {
+        let response = self.post(path).json(requestbody).send().await;
+        handle_response::<StatusRes>(response).await?;
+        Ok(())
+    }
+
+    /// See [MalojaClient::delete_scrobble].
+    pub async fn delete_scrobble_async(&self, time: DateTime<Utc>) -> Result<(), RequestError> {
+        let requestbody = ScrobbleTimestampReq {
+            timestamp: time.timestamp(),
+            key: self.api_key()?,
+        };
+        self.manage_async("/apis/mlj_1/delete_scrobble", &requestbody)
+            .await
+    }
+
+    /// Deletes the scrobble played at the given time. See [delete_scrobble].
+    pub fn delete_scrobble(&self, time: DateTime<Utc>) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.delete_scrobble_async(time).await })
+    }
+
+    /// See [MalojaClient::reparse_scrobble].
+    pub async fn reparse_scrobble_async(
+        &self,
+        time: DateTime<Utc>,
+    ) -> Result<ReparseResult, RequestError> {
+        let requestbody = ScrobbleTimestampReq {
+            timestamp: time.timestamp(),
+            key: self.api_key()?,
+        };
+        let response = self
+            .post("/apis/mlj_1/reparse_scrobble")
+            .json(&requestbody)
+            .send()
+            .await;
+        let response = handle_response::<ReparseScrobbleRes>(response).await?;
+        match response.scrobble {
+            None => Ok(ReparseResult::Unchanged),
+            Some(scrobble) => {
+                Scrobble::from_scrobblestrackres(scrobble).map(ReparseResult::Reparsed)
+            }
+        }
+    }
+
+    /// Parses the scrobble played at the given time again. See [reparse_scrobble].
+    pub fn reparse_scrobble(&self, time: DateTime<Utc>) -> Result<ReparseResult, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.reparse_scrobble_async(time).await })
+    }
+
+    /// See [MalojaClient::edit_artist].
+    pub async fn edit_artist_async(
+        &self,
+        artist: ArtistId,
+        name: String,
+    ) -> Result<(), RequestError> {
+        let requestbody = EditArtistReq {
+            id: artist.0,
+            name,
+            key: self.api_key()?,
+        };
+        self.manage_async("/apis/mlj_1/edit_artist", &requestbody)
+            .await
+    }
+
+    /// Renames an artist. See [edit_artist].
+    pub fn edit_artist(&self, artist: ArtistId, name: String) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.edit_artist_async(artist, name).await })
+    }
+
+    /// See [MalojaClient::edit_track].
+    pub async fn edit_track_async(
+        &self,
+        track: TrackId,
+        title: String,
+    ) -> Result<(), RequestError> {
+        let requestbody = EditTrackReq {
+            id: track.0,
+            title,
+            key: self.api_key()?,
+        };
+        self.manage_async("/apis/mlj_1/edit_track", &requestbody)
+            .await
+    }
+
+    /// Renames a track. See [edit_track].
+    pub fn edit_track(&self, track: TrackId, title: String) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.edit_track_async(track, title).await })
+    }
+
+    /// See [MalojaClient::edit_album].
+    pub async fn edit_album_async(
+        &self,
+        album: AlbumId,
+        title: String,
+    ) -> Result<(), RequestError> {
+        let requestbody = EditAlbumReq {
+            id: album.0,
+            albumtitle: title,
+            key: self.api_key()?,
+        };
+        self.gated(
+            Capability::Albums,
+            self.manage_async("/apis/mlj_1/edit_album", &requestbody),
+        )
+        .await
+    }
+
+    /// Renames an album. See [edit_album].
+    pub fn edit_album(&self, album: AlbumId, title: String) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.edit_album_async(album, title).await })
+    }
+
+    /// See [MalojaClient::merge_artists].
+    pub async fn merge_artists_async(
+        &self,
+        target: ArtistId,
+        sources: &[ArtistId],
+    ) -> Result<(), RequestError> {
+        let requestbody = MergeReq {
+            target_id: Some(target.0),
+            source_ids: sources.iter().map(|id| id.0).collect(),
+            remove: None,
+            key: self.api_key()?,
+        };
+        self.manage_async("/apis/mlj_1/merge_artists", &requestbody)
+            .await
+    }
+
+    /// Merges artists into another one. See [merge_artists].
+    pub fn merge_artists(
+        &self,
+        target: ArtistId,
+        sources: &[ArtistId],
+    ) -> Result<(), RequestError> {
+        let client = self.clone();
+        let sources = sources.to_vec();
+        block_on(async move { client.merge_artists_async(target, &sources).await })
+    }
+
+    /// See [MalojaClient::merge_tracks].
+    pub async fn merge_tracks_async(
+        &self,
+        target: TrackId,
+        sources: &[TrackId],
+    ) -> Result<(), RequestError> {
+        let requestbody = MergeReq {
+            target_id: Some(target.0),
+            source_ids: sources.iter().map(|id| id.0).collect(),
+            remove: None,
+            key: self.api_key()?,
+        };
+        self.manage_async("/apis/mlj_1/merge_tracks", &requestbody)
+            .await
+    }
+
+    /// Merges tracks into another one. See [merge_tracks].
+    pub fn merge_tracks(&self, target: TrackId, sources: &[TrackId]) -> Result<(), RequestError> {
+        let client = self.clone();
+        let sources = sources.to_vec();
+        block_on(async move { client.merge_tracks_async(target, &sources).await })
+    }
+
+    /// See [MalojaClient::merge_albums].
+    pub async fn merge_albums_async(
+        &self,
+        target: AlbumId,
+        sources: &[AlbumId],
+    ) -> Result<(), RequestError> {
+        let requestbody = MergeReq {
+            target_id: Some(target.0),
+            source_ids: sources.iter().map(|id| id.0).collect(),
+            remove: None,
+            key: self.api_key()?,
+        };
+        self.gated(
+            Capability::Albums,
+            self.manage_async("/apis/mlj_1/merge_albums", &requestbody),
+        )
+        .await
+    }
+
+    /// Merges albums into another one. See [merge_albums].
+    pub fn merge_albums(&self, target: AlbumId, sources: &[AlbumId]) -> Result<(), RequestError> {
+        let client = self.clone();
+        let sources = sources.to_vec();
+        block_on(async move { client.merge_albums_async(target, &sources).await })
+    }
+
+    /// See [MalojaClient::associate_albums_to_artist].
+    pub async fn associate_albums_to_artist_async(
+        &self,
+        artist: ArtistId,
+        albums: &[AlbumId],
+        remove: bool,
+    ) -> Result<(), RequestError> {
+        let requestbody = MergeReq {
+            target_id: Some(artist.0),
+            source_ids: albums.iter().map(|id| id.0).collect(),
+            remove: Some(remove),
+            key: self.api_key()?,
+        };
+        self.gated(
+            Capability::Albums,
+            self.manage_async("/apis/mlj_1/associate_albums_to_artist", &requestbody),
+        )
+        .await
+    }
+
+    /// Adds an artist to, or removes them from, the artists of albums. See [associate_albums_to_artist].
+    pub fn associate_albums_to_artist(
+        &self,
+        artist: ArtistId,
+        albums: &[AlbumId],
+        remove: bool,
+    ) -> Result<(), RequestError> {
+        let client = self.clone();
+        let albums = albums.to_vec();
+        block_on(async move {
+            client
+                .associate_albums_to_artist_async(artist, &albums, remove)
+                .await
+        })
+    }
+
+    /// See [MalojaClient::associate_tracks_to_album].
+    pub async fn associate_tracks_to_album_async(
+        &self,
+        album: Option<AlbumId>,
+        tracks: &[TrackId],
+    ) -> Result<(), RequestError> {
+        let requestbody = MergeReq {
+            target_id: album.map(|id| id.0),
+            source_ids: tracks.iter().map(|id| id.0).collect(),
+            remove: None,
+            key: self.api_key()?,
+        };
+        self.gated(
+            Capability::Albums,
+            self.manage_async("/apis/mlj_1/associate_tracks_to_album", &requestbody),
+        )
+        .await
+    }
+
+    /// Moves tracks to an album, or out of their album. See [associate_tracks_to_album].
+    pub fn associate_tracks_to_album(
+        &self,
+        album: Option<AlbumId>,
+        tracks: &[TrackId],
+    ) -> Result<(), RequestError> {
+        let client = self.clone();
+        let tracks = tracks.to_vec();
+        block_on(async move { client.associate_tracks_to_album_async(album, &tracks).await })
+    }
+}
+
+/// See [delete_scrobble].
+pub async fn delete_scrobble_async(
+    time: DateTime<Utc>,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .delete_scrobble_async(time)
+        .await
+}
+
+/// Deletes a scrobble, given the time it was played. Requires an API key.
+///
+/// # Arguments
+///
+/// * `time` - When the scrobble was played, as returned by e.g. [crate::history::scrobbles].
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::range::Range;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// let latest = mljcl::history::scrobbles(None, Range::Today, None, Some(1), creds.clone()).unwrap();
+/// if let Some(scrobble) = latest.first() {
+///     mljcl::manage::delete_scrobble(scrobble.time, creds).unwrap();
+/// }
+/// ```
+pub fn delete_scrobble(
+    time: DateTime<Utc>,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .delete_scrobble(time)
+}
+
+/// See [reparse_scrobble].
+pub async fn reparse_scrobble_async(
+    time: DateTime<Utc>,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<ReparseResult, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .reparse_scrobble_async(time)
+        .await
+}
+
+/// Parses a scrobble's raw data again, so that changes to the server's parsing rules apply to it.
+/// Requires an API key.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::manage::ReparseResult;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// # let time = chrono::Utc::now();
+/// match mljcl::manage::reparse_scrobble(time, creds).unwrap() {
+///     ReparseResult::Reparsed(scrobble) => println!("Now credited to {}", scrobble.track.artists.join(", ")),
+///     ReparseResult::Unchanged => println!("Nothing to change"),
+/// }
+/// ```
+pub fn reparse_scrobble(
+    time: DateTime<Utc>,
+    credentials: MalojaCredentials,
+) -> Result<ReparseResult, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .reparse_scrobble(time)
+}
+
+/// See [edit_artist].
+pub async fn edit_artist_async(
+    artist: ArtistId,
+    name: String,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .edit_artist_async(artist, name)
+        .await
+}
+
+/// Renames an artist. Requires an API key.
+///
+/// If another artist already has the new name, maloja merges the two.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::types::{ArtistId, Entity};
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// let info = mljcl::info::artist_info(Entity::Artist("Some artsit".to_string()), creds.clone()).unwrap();
+/// let id = ArtistId::try_from(&info.artist).unwrap();
+/// mljcl::manage::edit_artist(id, "Some artist".to_string(), creds).unwrap();
+/// ```
+pub fn edit_artist(
+    artist: ArtistId,
+    name: String,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .edit_artist(artist, name)
+}
+
+/// See [edit_track].
+pub async fn edit_track_async(
+    track: TrackId,
+    title: String,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .edit_track_async(track, title)
+        .await
+}
+
+/// Renames a track. Requires an API key.
+///
+/// A track's ID can be taken from one returned by e.g. [crate::info::track_info], with `TrackId::try_from`.
+pub fn edit_track(
+    track: TrackId,
+    title: String,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .edit_track(track, title)
+}
+
+/// See [edit_album].
+pub async fn edit_album_async(
+    album: AlbumId,
+    title: String,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .edit_album_async(album, title)
+        .await
+}
+
+/// Renames an album. Requires an API key.
+pub fn edit_album(
+    album: AlbumId,
+    title: String,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .edit_album(album, title)
+}
+
+/// See [merge_artists].
+pub async fn merge_artists_async(
+    target: ArtistId,
+    sources: &[ArtistId],
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .merge_artists_async(target, sources)
+        .await
+}
+
+/// Merges artists into another one, crediting all of their scrobbles to it. Requires an API key.
+///
+/// # Arguments
+///
+/// * `target` - The artist to keep.
+/// * `sources` - The artists to merge into `target`. These no longer exist afterwards.
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::types::ArtistId;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// // Artists 12 and 34 turned out to be the same artist under two names.
+/// mljcl::manage::merge_artists(ArtistId(12), &[ArtistId(34)], creds).unwrap();
+/// ```
+pub fn merge_artists(
+    target: ArtistId,
+    sources: &[ArtistId],
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .merge_artists(target, sources)
+}
+
+/// See [merge_tracks].
+pub async fn merge_tracks_async(
+    target: TrackId,
+    sources: &[TrackId],
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .merge_tracks_async(target, sources)
+        .await
+}
+
+/// Merges tracks into another one, crediting all of their scrobbles to it. Requires an API key.
+pub fn merge_tracks(
+    target: TrackId,
+    sources: &[TrackId],
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .merge_tracks(target, sources)
+}
+
+/// See [merge_albums].
+pub async fn merge_albums_async(
+    target: AlbumId,
+    sources: &[AlbumId],
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .merge_albums_async(target, sources)
+        .await
+}
+
+/// Merges albums into another one, moving all of their tracks to it. Requires an API key.
+pub fn merge_albums(
+    target: AlbumId,
+    sources: &[AlbumId],
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .merge_albums(target, sources)
+}
+
+/// See [associate_albums_to_artist].
+pub async fn associate_albums_to_artist_async(
+    artist: ArtistId,
+    albums: &[AlbumId],
+    remove: bool,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .associate_albums_to_artist_async(artist, albums, remove)
+        .await
+}
+
+/// Adds an artist to the artists of albums, or removes them if `remove` is `true`. Requires an API key.
+pub fn associate_albums_to_artist(
+    artist: ArtistId,
+    albums: &[AlbumId],
+    remove: bool,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .associate_albums_to_artist(artist, albums, remove)
+}
+
+/// See [associate_tracks_to_album].
+pub async fn associate_tracks_to_album_async(
+    album: Option<AlbumId>,
+    tracks: &[TrackId],
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .associate_tracks_to_album_async(album, tracks)
+        .await
+}
+
+/// Moves tracks to an album, or removes them from their album if `album` is `None`. Requires an API key.
+pub fn associate_tracks_to_album(
+    album: Option<AlbumId>,
+    tracks: &[TrackId],
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .associate_tracks_to_album(album, tracks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    fn body(request: &str) -> serde_json::Value {
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    fn time() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn delete_scrobble_sends_its_time() {
+        let (credentials, requests) = serve(vec![
+            (
+                200,
+                r#"{"status":"success","desc":"Scrobble was deleted!"}"#,
+            ),
+            (
+                404,
+                r#"{"status":"failure","error":{"type":"entity_does_not_exist","desc":"No scrobble at this time"}}"#,
+            ),
+        ]);
+        let client = MalojaClient::new(credentials).unwrap();
+        client.delete_scrobble(time()).unwrap();
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /apis/mlj_1/delete_scrobble "));
+        assert_eq!(
+            body(&request),
+            serde_json::json!({"timestamp": 1_700_000_000, "key": "key"})
+        );
+        assert!(matches!(
+            client.delete_scrobble(time()),
+            Err(RequestError::ServerError { error_type, .. }) if error_type == "entity_does_not_exist"
+        ));
+    }
+
+    #[test]
+    fn reparse_scrobble_reports_whether_it_changed() {
+        let (credentials, requests) = serve(vec![
+            (
+                200,
+                r#"{"status":"success","desc":"Scrobble was reparsed!","scrobble":
+                    {"time":1700000000,"track":{"artists":["A","B"],"title":"T"},"duration":null,"origin":null}}"#,
+            ),
+            (
+                200,
+                r#"{"status":"no_operation","desc":"The scrobble was not changed."}"#,
+            ),
+        ]);
+        let client = MalojaClient::new(credentials).unwrap();
+        let ReparseResult::Reparsed(scrobble) = client.reparse_scrobble(time()).unwrap() else {
+            panic!("the scrobble was not reparsed");
+        };
+        assert_eq!(scrobble.time, time());
+        assert_eq!(scrobble.track.artists, ["A", "B"]);
+        let request = requests.recv().unwrap();
+        assert!(request.starts_with("POST /apis/mlj_1/reparse_scrobble "));
+        assert_eq!(body(&request)["timestamp"], 1_700_000_000);
+        assert!(matches!(
+            client.reparse_scrobble(time()),
+            Ok(ReparseResult::Unchanged)
+        ));
+    }
+
+    const SUCCESS: &str = r#"{"status":"success"}"#;
+
+    #[test]
+    fn edits_send_the_id_and_new_name() {
+        let (credentials, requests) = serve(vec![(200, SUCCESS); 3]);
+        let client = MalojaClient::new(credentials).unwrap();
+        client.edit_artist(ArtistId(1), "A".to_string()).unwrap();
+        client.edit_track(TrackId(2), "T".to_string()).unwrap();
+        client.edit_album(AlbumId(3), "X".to_string()).unwrap();
+        let bodies: Vec<serde_json::Value> =
+            requests.iter().map(|request| body(&request)).collect();
+        assert_eq!(
+            bodies,
+            [
+                serde_json::json!({"id": 1, "name": "A", "key": "key"}),
+                serde_json::json!({"id": 2, "title": "T", "key": "key"}),
+                serde_json::json!({"id": 3, "albumtitle": "X", "key": "key"}),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_and_associations_send_their_ids() {
+        let (credentials, requests) = serve(vec![(200, SUCCESS); 4]);
+        let client = MalojaClient::new(credentials).unwrap();
+        client
+            .merge_artists(ArtistId(1), &[ArtistId(2), ArtistId(3)])
+            .unwrap();
+        client.merge_tracks(TrackId(4), &[TrackId(5)]).unwrap();
+        client
+            .associate_albums_to_artist(ArtistId(1), &[AlbumId(6)], true)
+            .unwrap();
+        client
+            .associate_tracks_to_album(None, &[TrackId(4)])
+            .unwrap();
+        let requests: Vec<String> = requests.iter().collect();
+        assert!(requests[0].starts_with("POST /apis/mlj_1/merge_artists "));
+        assert_eq!(
+            body(&requests[0]),
+            serde_json::json!({"target_id": 1, "source_ids": [2, 3], "key": "key"})
+        );
+        assert_eq!(
+            body(&requests[1]),
+            serde_json::json!({"target_id": 4, "source_ids": [5], "key": "key"})
+        );
+        assert!(requests[2].starts_with("POST /apis/mlj_1/associate_albums_to_artist "));
+        assert_eq!(
+            body(&requests[2]),
+            serde_json::json!({"target_id": 1, "source_ids": [6], "remove": true, "key": "key"})
+        );
+        assert_eq!(
+            body(&requests[3]),
+            serde_json::json!({"target_id": null, "source_ids": [4], "key": "key"})
+        );
+    }
+
+    #[test]
+    fn album_operations_need_albums() {
+        // Only the server info is served, so none of the album operations may be sent.
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{"name":"Old","version":[3,1,0],"versionstring":"3.1.0"}"#,
+        )]);
+        let client = MalojaClient::new(credentials).unwrap();
+        client.server_info().unwrap();
+        let unsupported = |result: Result<(), RequestError>| {
+            matches!(
+                result,
+                Err(RequestError::Unsupported {
+                    capability: Capability::Albums,
+                    ..
+                })
+            )
+        };
+        assert!(unsupported(client.edit_album(AlbumId(1), "X".to_string())));
+        assert!(unsupported(client.merge_albums(AlbumId(1), &[AlbumId(2)])));
+        assert!(unsupported(client.associate_albums_to_artist(
+            ArtistId(1),
+            &[AlbumId(2)],
+            false
+        )));
+        assert!(unsupported(
+            client.associate_tracks_to_album(Some(AlbumId(1)), &[TrackId(2)])
+        ));
+        assert_eq!(requests.iter().count(), 1);
+    }
+
+    #[test]
+    fn ids_must_be_numbers() {
+        let artist = Artist {
+            name: "A".to_string(),
+            id: "12".to_string(),
+        };
+        assert_eq!(ArtistId::try_from(&artist).unwrap(), ArtistId(12));
+        let album = Album {
+            name: "X".to_string(),
+            id: String::new(),
+            artists: None,
+        };
+        assert!(matches!(
+            AlbumId::try_from(&album),
+            Err(RequestError::InvalidId(_))
+        ));
+        let track = Track::new("T".to_string(), None, None, None, vec!["A".to_string()]);
+        assert!(matches!(
+            TrackId::try_from(&track),
+            Err(RequestError::InvalidId(reason)) if reason == "track `T` has no ID"
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::audioscrobbler::AudioscrobblerClient;
+use crate::block_on;
+use crate::client::MalojaClient;
+use crate::credentials::MalojaCredentials;
+use crate::errors::RequestError;
+use crate::submission::ScrobbleSubmission;
+use crate::types::Track;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use std::time::Duration;
+
+/// A track which is currently playing.
+#[derive(Clone, Debug)]
+pub struct NowPlaying {
+    /// The track.
+    pub track: Track,
+    /// When the track started playing. Once it has finished, this is the time to scrobble it with.
+    ///
+    /// This is sent as the `timestamp` of an Audioscrobbler notification. ListenBrainz doesn't allow
+    /// a time on `playing_now` listens, so it isn't sent through [MalojaClient::now_playing].
+    pub started_at: DateTime<Utc>,
+    /// Optionally, the full length of the track, which is sent with every notification.
+    pub length: Option<Duration>,
+}
+
+impl NowPlaying {
+    /// Creates a `NowPlaying` for a track which has just started playing.
+    pub fn new(track: Track, length: Option<Duration>) -> Self {
+        NowPlaying {
+            track,
+            started_at: Utc::now(),
+            length,
+        }
+    }
+}
+
+/// Turns the track into a scrobble played at `started_at`, e.g. to submit once it has finished.
+impl From<NowPlaying> for ScrobbleSubmission {
+    fn from(now_playing: NowPlaying) -> Self {
+        ScrobbleSubmission {
+            title: now_playing.track.name,
+            artists: now_playing.track.artists,
+            album: now_playing.track.album,
+            album_artists: now_playing.track.album_artists,
+            time: Some(now_playing.started_at),
+            duration: None,
+            length: now_playing.length,
+        }
+    }
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::now_playing].
+    pub async fn now_playing_async(&self, now_playing: NowPlaying) -> Result<(), RequestError> {
+        // maloja's own API has no now playing notifications, so they always go through
+        // ListenBrainz, whichever protocol scrobbles are submitted through.
+        self.playing_now_listen_async(now_playing.into()).await
+    }
+
+    /// Announces the track currently playing. See [now_playing].
+    pub fn now_playing(&self, now_playing: NowPlaying) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.now_playing_async(now_playing).await })
+    }
+}
+
+impl AudioscrobblerClient {
+    /// See [AudioscrobblerClient::now_playing].
+    pub async fn now_playing_async(&self, now_playing: NowPlaying) -> Result<(), RequestError> {
+        self.send_now_playing_async(now_playing.into(), true).await
+    }
+
+    /// Announces the track currently playing with `track.updateNowPlaying`, along with when it
+    /// started and its length.
+    ///
+    /// maloja accepts these notifications but discards them.
+    pub fn now_playing(&self, now_playing: NowPlaying) -> Result<(), RequestError> {
+        let client = self.clone();
+        block_on(async move { client.now_playing_async(now_playing).await })
+    }
+}
+
+/// See [now_playing].
+pub async fn now_playing_async(
+    now_playing: NowPlaying,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<(), RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .now_playing_async(now_playing)
+        .await
+}
+
+/// Announces the track currently playing. Requires an API key.
+///
+/// maloja's own API can't carry this, so the notification is sent through its
+/// ListenBrainz-compatible API as a `playing_now` listen, with the track's length but not
+/// its start time. To send it through the Audioscrobbler API instead, use
+/// [AudioscrobblerClient::now_playing].
+///
+/// maloja accepts now playing notifications through every API, but discards them, so this
+/// currently has no effect on a maloja server. It is meant for servers and proxies which
+/// act on them.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::now_playing::NowPlaying;
+/// use mljcl::types::Track;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build().unwrap();
+/// let track = Track::new("Some track".into(), None, None, None, vec!["Some artist".into()]);
+/// let playing = NowPlaying::new(track, Some(std::time::Duration::from_secs(215)));
+/// mljcl::now_playing::now_playing(playing.clone(), creds.clone()).unwrap();
+/// // Once the track has finished
+/// mljcl::submit_scrobble(playing.into(), creds).unwrap();
+/// ```
+pub fn now_playing(
+    now_playing: NowPlaying,
+    credentials: MalojaCredentials,
+) -> Result<(), RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .now_playing(now_playing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    fn now_playing() -> NowPlaying {
+        NowPlaying {
+            track: Track::new("Track".into(), None, None, None, vec!["Artist".into()]),
+            started_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            length: Some(Duration::from_secs(215)),
+        }
+    }
+
+    #[test]
+    fn audioscrobbler_sends_start_and_length() {
+        let (credentials, requests) = serve(vec![(200, r#"{"nowplaying":{}}"#)]);
+        let client = AudioscrobblerClient::from_credentials(&credentials)
+            .unwrap()
+            .with_session_key("SESSION".to_string());
+        client.now_playing(now_playing()).unwrap();
+        let request = requests.recv().unwrap();
+        assert!(request.contains("method=track.updateNowPlaying"));
+        assert!(request.contains("timestamp=1700000000"));
+        assert!(request.contains("duration=215"));
+    }
+
+    #[test]
+    fn listenbrainz_sends_length_without_a_time() {
+        let (credentials, requests) = serve(vec![(200, r#"{"status":"ok"}"#)]);
+        MalojaClient::new(credentials)
+            .unwrap()
+            .now_playing(now_playing())
+            .unwrap();
+        let request = requests.recv().unwrap();
+        let (_, body) = request.split_once("\r\n\r\n").unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["listen_type"], "playing_now");
+        let listen = &body["payload"][0];
+        assert!(listen.get("listened_at").is_none());
+        assert_eq!(listen["track_metadata"]["additional_info"]["duration"], 215);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::{block_on, full_query_path, handle_response, require_field, MalojaCredentials};
+use crate::{
+    json::*,
+    range::{process_range, Range, Step},
+    types::*,
+};
+use reqwest::Client;
+
+impl MalojaClient {
+    /// See [MalojaClient::performance].
+    pub async fn performance_async(
+        &self,
+        entity: Entity,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<u64>)>, RequestError> {
+        let from_until_in = process_range(range)?;
+        let requestbody = PerformanceReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+            step: step.to_string(),
+            stepn,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/performance")?)
+            .query(&entity.query_pairs())
+            .send()
+            .await;
+        match handle_response::<PerformanceRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut ranks: Vec<(Range, Option<u64>)> = vec![];
+                for entry in require_field(response.list, "list")? {
+                    ranks.push((Range::from_rangeres(&entry.range)?, entry.rank));
+                }
+                Ok(ranks)
+            }
+        }
+    }
+
+    /// Fetches the chart rank of an artist, track or album in each period of a time range.
+    /// See [performance].
+    pub fn performance(
+        &self,
+        entity: Entity,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<u64>)>, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.performance_async(entity, range, step, stepn).await })
+    }
+}
+
+/// See [performance].
+pub async fn performance_async(
+    entity: Entity,
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<Vec<(Range, Option<u64>)>, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .performance_async(entity, range, step, stepn)
+        .await
+}
+
+/// Fetches the chart rank of an artist, track or album in each period of a time range.
+/// The rank is `None` for periods in which it didn't make the charts.
+///
+/// # Arguments
+///
+/// * `entity` - The artist, track or album to rank.
+/// * `range` - A time range.
+/// * `step` - The unit of time to divide the range into.
+/// * `stepn` - How many of those units each period spans.
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::range::{Range, Step};
+/// use mljcl::types::Entity;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let artist = Entity::Artist("Some artist".to_string());
+/// for (week, rank) in mljcl::performance::performance(artist, Range::ThisYear, Step::Week, 1, creds).unwrap() {
+///     match rank {
+///         Some(rank) => println!("{}: #{}", week, rank),
+///         None => println!("{}: not ranked", week),
+///     }
+/// }
+/// ```
+pub fn performance(
+    entity: Entity,
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+) -> Result<Vec<(Range, Option<u64>)>, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .performance(entity, range, step, stepn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    #[test]
+    fn performance_keeps_periods_without_a_rank() {
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{"status":"ok","list":[
+                {"range":{"fromstring":"2023","tostring":"2023","fromstamp":1672531200,"tostamp":1704067200,"description":"2023"},"rank":null},
+                {"range":{"fromstring":"2024","tostring":"2024","fromstamp":1704067200,"tostamp":1735689600,"description":"2024"},"rank":3}
+            ]}"#,
+        )]);
+        let ranks = MalojaClient::new(credentials)
+            .unwrap()
+            .performance(
+                Entity::ArtistId("7".to_string()),
+                Range::Since(chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()),
+                Step::Year,
+                1,
+            )
+            .unwrap();
+        assert_eq!(
+            ranks,
+            vec![(Range::Year(2023), None), (Range::Year(2024), Some(3))]
+        );
+        let request = requests.recv().unwrap();
+        let path = request.lines().next().unwrap();
+        assert!(path.contains("step=year"));
+        assert!(path.contains("artist_id=7"));
+    }
+}
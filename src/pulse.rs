@@ -0,0 +1,142 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::{block_on, full_query_path, handle_response, require_field, MalojaCredentials};
+use crate::{
+    json::*,
+    range::{process_range, Range, Step},
+    types::*,
+};
+use reqwest::Client;
+
+impl MalojaClient {
+    /// See [MalojaClient::pulse].
+    pub async fn pulse_async(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+        entity: Option<Entity>,
+    ) -> Result<Vec<(Range, u64)>, RequestError> {
+        let from_until_in = process_range(range)?;
+        let requestbody = PulseReq {
+            from: from_until_in.0,
+            until: from_until_in.1,
+            _in: from_until_in.2,
+            step: step.to_string(),
+            stepn,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/pulse")?)
+            .query(
+                &entity
+                    .map(|entity| entity.query_pairs())
+                    .unwrap_or_default(),
+            )
+            .send()
+            .await;
+        match handle_response::<PulseRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut pulse: Vec<(Range, u64)> = vec![];
+                for entry in require_field(response.list, "list")? {
+                    pulse.push((Range::from_rangeres(&entry.range)?, entry.scrobbles));
+                }
+                Ok(pulse)
+            }
+        }
+    }
+
+    /// Counts scrobbles in each period of a time range. See [pulse].
+    pub fn pulse(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+        entity: Option<Entity>,
+    ) -> Result<Vec<(Range, u64)>, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.pulse_async(range, step, stepn, entity).await })
+    }
+}
+
+/// See [pulse].
+pub async fn pulse_async(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    entity: Option<Entity>,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<Vec<(Range, u64)>, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .pulse_async(range, step, stepn, entity)
+        .await
+}
+
+/// Counts scrobbles in each period of a time range, optionally only for a certain artist, track or album.
+///
+/// # Arguments
+///
+/// * `range` - A time range.
+/// * `step` - The unit of time to divide the range into.
+/// * `stepn` - How many of those units each period spans.
+/// * `entity` - Optionally, the artist, track or album to count scrobbles of.
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::range::{Range, Step};
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// for (month, scrobbles) in mljcl::pulse::pulse(Range::ThisYear, Step::Month, 1, None, creds).unwrap() {
+///     println!("{}: {} scrobbles", month, scrobbles);
+/// }
+/// ```
+pub fn pulse(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    entity: Option<Entity>,
+    credentials: MalojaCredentials,
+) -> Result<Vec<(Range, u64)>, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .pulse(range, step, stepn, entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    #[test]
+    fn pulse_is_counted_per_period() {
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{"status":"ok","list":[
+                {"range":{"fromstring":"2024/01","tostring":"2024/01","fromstamp":1704067200,"tostamp":1706745600,"description":"January 2024"},"scrobbles":12},
+                {"range":{"fromstring":"2024/02","tostring":"2024/02","fromstamp":1706745600,"tostamp":1709251200,"description":"February 2024"},"scrobbles":0}
+            ]}"#,
+        )]);
+        let range = Range::FromTo(
+            chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+        );
+        let pulse = MalojaClient::new(credentials)
+            .unwrap()
+            .pulse(range, Step::Month, 1, Some(Entity::Artist("A".to_string())))
+            .unwrap();
+        assert_eq!(
+            pulse,
+            vec![
+                (Range::month(2024, 1).unwrap(), 12),
+                (Range::month(2024, 2).unwrap(), 0),
+            ]
+        );
+        let request = requests.recv().unwrap();
+        let path = request.lines().next().unwrap();
+        assert!(path.contains("step=month"));
+        assert!(path.contains("artist=A"));
+    }
+}
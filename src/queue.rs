@@ -0,0 +1,458 @@
+use crate::block_on;
+use crate::client::MalojaClient;
+use crate::errors::{QueueError, RequestError};
+use crate::submission::ScrobbleSubmission;
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A scrobble waiting in a [ScrobbleQueue] to be submitted.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct QueuedScrobble {
+    /// The scrobble itself. Its time is always set, so the play keeps its original timestamp.
+    pub submission: ScrobbleSubmission,
+    /// When the scrobble was added to the queue.
+    pub queued_at: DateTime<Utc>,
+    /// How many times submitting this scrobble has failed so far.
+    pub attempts: u32,
+}
+
+/// How a [ScrobbleQueue] retries a scrobble while the server can't be reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times to try submitting a scrobble during a single flush before giving up.
+    pub max_attempts: u32,
+    /// How long to wait after the first failed attempt. Doubles with every further attempt.
+    pub initial_backoff: Duration,
+    /// The longest to ever wait between two attempts.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff)
+    }
+}
+
+/// The outcome of [ScrobbleQueue::flush].
+#[derive(Debug, Default)]
+pub struct FlushReport {
+    /// Scrobbles which the server accepted, in the order they were submitted.
+    pub submitted: Vec<QueuedScrobble>,
+    /// Scrobbles which maloja refused with an error of its own. These are removed from the queue,
+    /// since retrying won't help.
+    pub rejected: Vec<(QueuedScrobble, RequestError)>,
+    /// Scrobbles still left in the queue.
+    pub pending: Vec<QueuedScrobble>,
+    /// If the flush stopped early because the server couldn't be reached, refused the
+    /// credentials or gave a response which didn't come from maloja, the last error it gave.
+    pub error: Option<RequestError>,
+}
+
+/// A queue of scrobbles stored on disk as one JSON object per line, so plays made while the
+/// server is down aren't lost.
+///
+/// Scrobbles are always flushed in the order they were queued. Flushing stops at the first
+/// scrobble which can't be delivered, leaving it and everything after it in the queue.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let client = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).api_key("key".into()).build_client().unwrap();
+/// # let submission = mljcl::submission::ScrobbleSubmission::builder().title("Some track".into()).artist("Some artist".into()).build().unwrap();
+/// let mut queue = mljcl::queue::ScrobbleQueue::open("scrobbles.jsonl").unwrap();
+/// let report = queue.submit(submission, &client).unwrap();
+/// println!("{} scrobbles still waiting for the server", report.pending.len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScrobbleQueue {
+    path: PathBuf,
+    entries: Vec<QueuedScrobble>,
+    retry_policy: RetryPolicy,
+}
+
+impl ScrobbleQueue {
+    /// Opens the queue stored at `path`, creating an empty one if the file doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ScrobbleQueue, QueueError> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = vec![];
+        match File::open(&path) {
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(QueueError::IoError(error)),
+            Ok(file) => {
+                for (index, line) in BufReader::new(file).lines().enumerate() {
+                    let line = line.map_err(QueueError::IoError)?;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    entries.push(serde_json::from_str(&line).map_err(|error| {
+                        QueueError::CorruptEntry {
+                            line: index + 1,
+                            error,
+                        }
+                    })?);
+                }
+            }
+        }
+        Ok(ScrobbleQueue {
+            path,
+            entries,
+            retry_policy: RetryPolicy::default(),
+        })
+    }
+
+    /// Sets how scrobbles are retried while flushing.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> ScrobbleQueue {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// The file this queue is stored in.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The scrobbles waiting to be submitted, oldest first.
+    pub fn pending(&self) -> &[QueuedScrobble] {
+        &self.entries
+    }
+
+    /// The amount of scrobbles waiting to be submitted.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no scrobbles waiting to be submitted.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds a scrobble to the end of the queue and writes it to disk.
+    /// If the scrobble has no time set, it is given the current time.
+    pub fn push(&mut self, mut submission: ScrobbleSubmission) -> Result<(), QueueError> {
+        let now = Utc::now();
+        submission.time.get_or_insert(now);
+        let entry = QueuedScrobble {
+            submission,
+            queued_at: now,
+            attempts: 0,
+        };
+        let mut line = serde_json::to_string(&entry).map_err(|error| QueueError::CorruptEntry {
+            line: self.entries.len() + 1,
+            error,
+        })?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(QueueError::IoError)?;
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(QueueError::IoError)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// See [ScrobbleQueue::flush].
+    pub async fn flush_async(&mut self, client: &MalojaClient) -> Result<FlushReport, QueueError> {
+        let mut report = FlushReport::default();
+        while let Some(entry) = self.entries.first_mut() {
+            let mut attempt = 0;
+            let result = loop {
+                match client.submit_scrobble_async(entry.submission.clone()).await {
+                    Err(error) if is_retryable(&error) => {
+                        entry.attempts += 1;
+                        attempt += 1;
+                        if attempt >= self.retry_policy.max_attempts {
+                            break Err(error);
+                        }
+                        tokio::time::sleep(self.retry_policy.backoff(attempt - 1)).await;
+                    }
+                    Err(error) if is_rejection(&error) => break Ok(Err(error)),
+                    // Anything else, like bad credentials or a response which didn't come from
+                    // maloja, may mean the scrobble never arrived, so it is kept.
+                    Err(error) => break Err(error),
+                    Ok(_) => break Ok(Ok(())),
+                }
+            };
+            match result {
+                Err(error) => {
+                    report.error = Some(error);
+                    break;
+                }
+                Ok(result) => {
+                    let entry = self.entries.remove(0);
+                    match result {
+                        Ok(_) => report.submitted.push(entry),
+                        Err(error) => report.rejected.push((entry, error)),
+                    }
+                }
+            }
+            self.save()?;
+        }
+        // Failed attempts are counted even if nothing could be delivered.
+        self.save()?;
+        report.pending = self.entries.clone();
+        Ok(report)
+    }
+
+    /// Submits every queued scrobble in order, retrying with backoff while the server can't be reached.
+    pub fn flush(&mut self, client: &MalojaClient) -> Result<FlushReport, QueueError> {
+        let mut queue = self.clone();
+        let client = client.clone();
+        let entries = self.entries.clone();
+        match block_on(async move {
+            let report = queue.flush_async(&client).await;
+            Ok::<_, RequestError>((queue, report))
+        }) {
+            Ok((queue, report)) => {
+                *self = queue;
+                report
+            }
+            // Nothing could be sent, so the queue is unchanged.
+            Err(error) => Ok(FlushReport {
+                pending: entries,
+                error: Some(error),
+                ..FlushReport::default()
+            }),
+        }
+    }
+
+    /// See [ScrobbleQueue::submit].
+    pub async fn submit_async(
+        &mut self,
+        submission: ScrobbleSubmission,
+        client: &MalojaClient,
+    ) -> Result<FlushReport, QueueError> {
+        self.push(submission)?;
+        self.flush_async(client).await
+    }
+
+    /// Queues a scrobble and then flushes the queue, so that it is submitted right away
+    /// if the server is reachable, after any scrobbles queued before it.
+    pub fn submit(
+        &mut self,
+        submission: ScrobbleSubmission,
+        client: &MalojaClient,
+    ) -> Result<FlushReport, QueueError> {
+        self.push(submission)?;
+        self.flush(client)
+    }
+
+    fn save(&self) -> Result<(), QueueError> {
+        let mut contents = String::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            contents +=
+                &serde_json::to_string(entry).map_err(|error| QueueError::CorruptEntry {
+                    line: index + 1,
+                    error,
+                })?;
+            contents.push('\n');
+        }
+        // Write to a temporary file first so the queue is never left half written.
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let mut file = File::create(&temporary).map_err(QueueError::IoError)?;
+        file.write_all(contents.as_bytes())
+            .and_then(|_| file.sync_data())
+            .and_then(|_| std::fs::rename(&temporary, &self.path))
+            .map_err(QueueError::IoError)
+    }
+}
+
+// Only errors where the scrobble never reached maloja, such as the server being down or
+// a proxy in front of it failing or throttling requests, are worth retrying.
+fn is_retryable(error: &RequestError) -> bool {
+    match error {
+        RequestError::TransportError(_) => true,
+        RequestError::StatusError { status, .. } => {
+            status.is_server_error()
+                || *status == StatusCode::REQUEST_TIMEOUT
+                || *status == StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
+
+// A scrobble is only dropped when maloja itself answered with an error, e.g. a 400 saying a
+// field is missing, since resending it would be refused the same way.
+fn is_rejection(error: &RequestError) -> bool {
+    matches!(error, RequestError::ServerError { .. })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    fn queue_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("mljcl-queue-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn submission(title: &str) -> ScrobbleSubmission {
+        ScrobbleSubmission::builder()
+            .title(title.to_string())
+            .artist("Artist".to_string())
+            .build()
+            .unwrap()
+    }
+
+    fn titles(entries: &[QueuedScrobble]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.submission.title.as_str())
+            .collect()
+    }
+
+    fn client(responses: Vec<(u16, &str)>) -> MalojaClient {
+        MalojaClient::new(serve(responses).0).unwrap()
+    }
+
+    fn no_retries(queue: ScrobbleQueue) -> ScrobbleQueue {
+        queue.retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+    }
+
+    #[test]
+    fn push_and_open_round_trip_in_order() {
+        let path = queue_path("round-trip");
+        let mut queue = ScrobbleQueue::open(&path).unwrap();
+        assert!(queue.is_empty());
+        for title in ["first", "second", "third"] {
+            queue.push(submission(title)).unwrap();
+        }
+        let reopened = ScrobbleQueue::open(&path).unwrap();
+        assert_eq!(reopened.pending(), queue.pending());
+        assert_eq!(titles(reopened.pending()), ["first", "second", "third"]);
+        assert!(reopened
+            .pending()
+            .iter()
+            .all(|entry| entry.submission.time.is_some()));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_reports_corrupt_lines() {
+        let path = queue_path("corrupt");
+        let mut queue = ScrobbleQueue::open(&path).unwrap();
+        queue.push(submission("first")).unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"\nnot json\n")
+            .unwrap();
+        assert!(matches!(
+            ScrobbleQueue::open(&path),
+            Err(QueueError::CorruptEntry { line: 3, .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn only_server_errors_are_rejections() {
+        let status = |status: u16| RequestError::StatusError {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: "<html></html>".to_string(),
+        };
+        for code in [408, 429, 500, 502, 503] {
+            assert!(is_retryable(&status(code)), "{}", code);
+            assert!(!is_rejection(&status(code)), "{}", code);
+        }
+        for code in [400, 404, 422] {
+            assert!(!is_retryable(&status(code)), "{}", code);
+            assert!(!is_rejection(&status(code)), "{}", code);
+        }
+        let decode = RequestError::DecodeError {
+            error: serde_json::from_str::<serde_json::Value>("<html>").unwrap_err(),
+            body: "<html>".to_string(),
+        };
+        assert!(!is_retryable(&decode));
+        assert!(!is_rejection(&decode));
+        let server = RequestError::ServerError {
+            error_type: "missing_scrobble_data".to_string(),
+            desc: "Scrobble is missing data".to_string(),
+        };
+        assert!(!is_retryable(&server));
+        assert!(is_rejection(&server));
+        assert!(!is_rejection(&RequestError::MissingApiKey));
+        assert!(!is_rejection(&RequestError::Unauthorized(
+            "key".to_string()
+        )));
+    }
+
+    #[test]
+    fn flush_submits_in_order_and_drops_only_rejections() {
+        let path = queue_path("flush");
+        let client = client(vec![
+            (200, r#"{"status":"success"}"#),
+            (
+                400,
+                r#"{"status":"error","error":{"type":"missing_scrobble_data","desc":"no title"}}"#,
+            ),
+            (200, r#"{"status":"success"}"#),
+        ]);
+        let mut queue = ScrobbleQueue::open(&path).unwrap();
+        for title in ["first", "second", "third"] {
+            queue.push(submission(title)).unwrap();
+        }
+        let report = queue.flush(&client).unwrap();
+        assert!(report.error.is_none());
+        assert_eq!(titles(&report.submitted), ["first", "third"]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].0.submission.title, "second");
+        assert!(queue.is_empty());
+        assert!(ScrobbleQueue::open(&path).unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn flush_keeps_scrobbles_which_may_not_have_arrived() {
+        for (status, body) in [
+            (429, "slow down"),
+            (404, "<html>Not Found</html>"),
+            (200, "<html>Login</html>"),
+            (
+                401,
+                r#"{"status":"failure","error":{"type":"auth","desc":"bad key"}}"#,
+            ),
+        ] {
+            let path = queue_path("kept");
+            let client = client(vec![(200, r#"{"status":"success"}"#), (status, body)]);
+            let mut queue = no_retries(ScrobbleQueue::open(&path).unwrap());
+            for title in ["first", "second", "third"] {
+                queue.push(submission(title)).unwrap();
+            }
+            let report = queue.flush(&client).unwrap();
+            assert!(report.error.is_some(), "{} {}", status, body);
+            assert_eq!(titles(&report.submitted), ["first"]);
+            assert!(report.rejected.is_empty());
+            assert_eq!(titles(&report.pending), ["second", "third"]);
+            let reopened = ScrobbleQueue::open(&path).unwrap();
+            assert_eq!(titles(reopened.pending()), ["second", "third"]);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
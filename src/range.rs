@@ -1,35 +1,602 @@
+use crate::errors::{ParseRangeError, RequestError};
+use crate::json::RangeRes;
+use chrono::{DateTime, Datelike, Days, IsoWeek, Local, Months, NaiveDate, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
 /// A date range for a maloja operation.
-/// Can either be all time, within a time period (e.g. "today", "thisyear", "2024/01/01"), or within two dates.
+/// Can either be all time, one of maloja's relative keywords (e.g. "today", "thisyear"),
+/// a calendar year, month, ISO week or day, or a range between two dates.
+///
+/// Ranges can be parsed from and displayed as maloja's syntax: `YYYY`, `YYYY/MM`, `YYYY/wNN` and `YYYY/MM/DD`.
+/// Ranges between dates are written as `from-until`, where either side may be left empty.
+///
+/// # Examples
+///
+/// ```
+/// use mljcl::range::Range;
 ///
-/// Dates are in YYYY/MM/DD format.
-#[derive(PartialEq)]
+/// let month: Range = "2024/02".parse().unwrap();
+/// assert_eq!(month, Range::month(2024, 2).unwrap());
+/// assert_eq!(month.to_string(), "2024/02");
+/// assert!("thismonth ".parse::<Range>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Range {
     /// All time.
     AllTime,
-    /// Within a time period (e.g. "today", "thisyear", "2024/01/01").
-    In(String),
-    /// Within two dates.
-    FromTo((String, String)),
+    /// Today.
+    Today,
+    /// Yesterday.
+    Yesterday,
+    /// The current week.
+    ThisWeek,
+    /// The week before the current one.
+    LastWeek,
+    /// The current month.
+    ThisMonth,
+    /// The month before the current one.
+    LastMonth,
+    /// The current year.
+    ThisYear,
+    /// The year before the current one.
+    LastYear,
+    /// The last N days, including today.
+    ///
+    /// maloja has no keyword for this, so it is sent to the server as a range between two dates,
+    /// counted from today's date on this machine.
+    LastDays(u32),
+    /// A calendar year.
+    Year(i32),
+    /// A calendar month.
+    Month(YearMonth),
+    /// An ISO week.
+    Week(IsoWeek),
+    /// A single day.
+    Day(NaiveDate),
+    /// Every day from the first date until the second, inclusive.
+    FromTo(NaiveDate, NaiveDate),
+    /// Every day from the given date onwards.
+    Since(NaiveDate),
+    /// Every day up to and including the given date.
+    Until(NaiveDate),
 }
 
-#[allow(missing_docs)]
-pub fn process_range(range: Range) -> (Option<String>, Option<String>, Option<String>) {
-    let mut from: Option<String> = None;
-    let mut until: Option<String> = None;
-    let mut _in: Option<String> = None;
-    match range {
-        Range::AllTime => {
-            from = None;
-            until = None;
-            _in = None;
+/// A calendar month which is known to exist, like chrono's `IsoWeek` for weeks.
+/// Created with [YearMonth::new] or [Range::month].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct YearMonth(NaiveDate);
+
+impl YearMonth {
+    /// The calendar month `month` (from 1 to 12) of `year`, if it exists.
+    pub fn new(year: i32, month: u32) -> Option<YearMonth> {
+        NaiveDate::from_ymd_opt(year, month, 1).map(YearMonth)
+    }
+
+    /// The year the month is in.
+    pub fn year(&self) -> i32 {
+        self.0.year()
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u32 {
+        self.0.month()
+    }
+
+    /// The first day of the month.
+    pub fn first_day(&self) -> NaiveDate {
+        self.0
+    }
+}
+
+/// A unit of time to divide ranges into, like maloja's `step` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Step {
+    /// Single days.
+    Day,
+    /// ISO weeks.
+    Week,
+    /// Calendar months.
+    Month,
+    /// Calendar years.
+    Year,
+}
+
+impl Step {
+    // The calendar unit of this size containing `date`.
+    fn containing(&self, date: NaiveDate) -> Range {
+        match self {
+            Step::Day => Range::Day(date),
+            Step::Week => Range::Week(date.iso_week()),
+            Step::Month => month_containing(date),
+            Step::Year => Range::Year(date.year()),
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Day => write!(f, "day"),
+            Step::Week => write!(f, "week"),
+            Step::Month => write!(f, "month"),
+            Step::Year => write!(f, "year"),
         }
-        Range::In(range) => {
-            _in = Some(range);
+    }
+}
+
+impl Range {
+    /// The calendar month `month` of `year`, if it exists.
+    pub fn month(year: i32, month: u32) -> Option<Range> {
+        YearMonth::new(year, month).map(Range::Month)
+    }
+
+    /// ISO week `week` of `year`, if it exists.
+    pub fn week(year: i32, week: u32) -> Option<Range> {
+        NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+            .map(|date| Range::Week(date.iso_week()))
+    }
+
+    /// The day `year`/`month`/`day`, if it exists.
+    pub fn day(year: i32, month: u32, day: u32) -> Option<Range> {
+        NaiveDate::from_ymd_opt(year, month, day).map(Range::Day)
+    }
+
+    /// Replaces relative ranges like `Today`, `LastMonth` or `LastDays` with the calendar range
+    /// they currently stand for, using today's date on this machine. Other ranges are returned as they are.
+    pub fn resolve(&self) -> Range {
+        self.resolve_on(Local::now().date_naive())
+    }
+
+    /// Like [Range::resolve], but as if today was `today`.
+    pub fn resolve_on(&self, today: NaiveDate) -> Range {
+        let resolved = match *self {
+            Range::Today => Some(Range::Day(today)),
+            Range::Yesterday => today.pred_opt().map(Range::Day),
+            Range::ThisWeek => Some(Range::Week(today.iso_week())),
+            Range::LastWeek => today
+                .checked_sub_days(Days::new(7))
+                .map(|date| Range::Week(date.iso_week())),
+            Range::ThisMonth => Some(month_containing(today)),
+            Range::LastMonth => today
+                .checked_sub_months(Months::new(1))
+                .map(month_containing),
+            Range::ThisYear => Some(Range::Year(today.year())),
+            Range::LastYear => Some(Range::Year(today.year() - 1)),
+            Range::LastDays(days) => today
+                .checked_sub_days(Days::new(u64::from(days.max(1)) - 1))
+                .map(|from| Range::FromTo(from, today)),
+            range => Some(range),
+        };
+        resolved.unwrap_or(*self)
+    }
+
+    /// The first and last day covered by this range, or `None` on a side where it is open-ended.
+    /// Relative ranges are resolved with [Range::resolve] first.
+    pub fn bounds(&self) -> (Option<NaiveDate>, Option<NaiveDate>) {
+        match self.resolve() {
+            Range::FromTo(from, until) => (Some(from), Some(until)),
+            Range::Since(from) => (Some(from), None),
+            Range::Until(until) => (None, Some(until)),
+            range => match range.calendar_bounds() {
+                Some((from, until)) => (Some(from), Some(until)),
+                None => (None, None),
+            },
+        }
+    }
+
+    /// Moves this range `n` periods forwards, or backwards if `n` is negative. A year moves by years,
+    /// a week by weeks and so on, while a range between two dates moves by its own length.
+    ///
+    /// Returns `None` for ranges which are open-ended, and for dates chrono can't represent.
+    pub fn shift(&self, n: i32) -> Option<Range> {
+        let months = Months::new(n.unsigned_abs());
+        let days = |length: u64| Days::new(length * u64::from(n.unsigned_abs()));
+        let add_days = |date: NaiveDate, length: u64| match n < 0 {
+            true => date.checked_sub_days(days(length)),
+            false => date.checked_add_days(days(length)),
+        };
+        match self.resolve() {
+            Range::Year(year) => year.checked_add(n).map(Range::Year),
+            Range::Month(month) => {
+                let first = month.first_day();
+                match n < 0 {
+                    true => first.checked_sub_months(months),
+                    false => first.checked_add_months(months),
+                }
+                .map(month_containing)
+            }
+            Range::Week(week) => add_days(
+                NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)?,
+                7,
+            )
+            .map(|date| Range::Week(date.iso_week())),
+            Range::Day(date) => add_days(date, 1).map(Range::Day),
+            Range::FromTo(from, until) => {
+                let length = u64::try_from((until - from).num_days() + 1).ok()?;
+                Some(Range::FromTo(
+                    add_days(from, length)?,
+                    add_days(until, length)?,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// The period after this one. See [Range::shift].
+    pub fn next(&self) -> Option<Range> {
+        self.shift(1)
+    }
+
+    /// The period before this one. See [Range::shift].
+    pub fn previous(&self) -> Option<Range> {
+        self.shift(-1)
+    }
+
+    /// Splits this range into every day, week, month or year it overlaps, in order.
+    /// Returns `None` if the range is open-ended. See [Range::steps].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use mljcl::range::{Range, Step};
+    ///
+    /// let quarter: Range = "2024/01-2024/03".parse().unwrap();
+    /// let months = quarter.split(Step::Month).unwrap();
+    /// assert_eq!(months, vec![
+    ///     Range::month(2024, 1).unwrap(),
+    ///     Range::month(2024, 2).unwrap(),
+    ///     Range::month(2024, 3).unwrap(),
+    /// ]);
+    /// ```
+    pub fn split(&self, step: Step) -> Option<Vec<Range>> {
+        self.steps(step, 1)
+    }
+
+    /// Divides this range into consecutive periods of `stepn` days, weeks, months or years,
+    /// like maloja's `step` and `stepn` parameters. The first period starts at the beginning
+    /// of the unit containing the start of this range, and the last one contains its end.
+    ///
+    /// Periods of a single unit are returned as that unit (e.g. `Range::Month`),
+    /// while longer ones are returned as `Range::FromTo`.
+    /// Returns `None` if the range is open-ended or `stepn` is 0.
+    pub fn steps(&self, step: Step, stepn: u32) -> Option<Vec<Range>> {
+        let (Some(from), Some(until)) = self.bounds() else {
+            return None;
+        };
+        let stepn = i32::try_from(stepn).ok().filter(|stepn| *stepn > 0)?;
+        let mut periods = vec![];
+        let mut current = step.containing(from);
+        loop {
+            let (start, _) = current.calendar_bounds()?;
+            if start > until {
+                break;
+            }
+            let last = current.shift(stepn - 1)?;
+            periods.push(match stepn {
+                1 => current,
+                _ => Range::FromTo(start, last.calendar_bounds()?.1),
+            });
+            current = last.next()?;
         }
-        Range::FromTo(range) => {
-            from = Some(range.0);
-            until = Some(range.1);
+        Some(periods)
+    }
+
+    /// Converts maloja's description of a range in a response back into a `Range`.
+    pub fn from_rangeres(range: &RangeRes) -> Result<Range, RequestError> {
+        let parse = |descriptor: &Option<String>| {
+            descriptor
+                .as_deref()
+                .and_then(|descriptor| descriptor.parse::<Range>().ok())
+        };
+        let stamp = |stamp: Option<i64>| {
+            stamp
+                .and_then(|stamp| DateTime::from_timestamp(stamp, 0))
+                .map(|time| time.date_naive())
+        };
+        let (from, until) = match (parse(&range.fromstring), parse(&range.tostring)) {
+            (Some(from), Some(until)) if from == until => return Ok(from),
+            bounds => bounds,
+        };
+        let first = from
+            .and_then(|from| from.bounds().0)
+            .or_else(|| stamp(range.fromstamp));
+        let last = until
+            .and_then(|until| until.bounds().1)
+            .or_else(|| stamp(range.tostamp));
+        match (first, last) {
+            (Some(first), Some(last)) => Ok(Range::FromTo(first, last)),
+            (Some(first), None) => Ok(Range::Since(first)),
+            (None, Some(last)) => Ok(Range::Until(last)),
+            (None, None) => Err(RequestError::MalformedResponse(format!(
+                "could not understand range {:?}",
+                range
+            ))),
+        }
+    }
+
+    // The first and last day of a calendar year, month, ISO week or day.
+    fn calendar_bounds(&self) -> Option<(NaiveDate, NaiveDate)> {
+        match *self {
+            Range::Year(year) => Some((
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year, 12, 31)?,
+            )),
+            Range::Month(month) => {
+                let first = month.first_day();
+                Some((first, first.checked_add_months(Months::new(1))?.pred_opt()?))
+            }
+            Range::Week(week) => Some((
+                NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)?,
+                NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Sun)?,
+            )),
+            Range::Day(date) => Some((date, date)),
+            _ => None,
+        }
+    }
+}
+
+fn month_containing(date: NaiveDate) -> Range {
+    Range::Month(YearMonth(date.with_day(1).unwrap_or(date)))
+}
+
+fn format_date(date: &NaiveDate) -> String {
+    date.format("%Y/%m/%d").to_string()
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Range::AllTime => write!(f, "alltime"),
+            Range::Today => write!(f, "today"),
+            Range::Yesterday => write!(f, "yesterday"),
+            Range::ThisWeek => write!(f, "thisweek"),
+            Range::LastWeek => write!(f, "lastweek"),
+            Range::ThisMonth => write!(f, "thismonth"),
+            Range::LastMonth => write!(f, "lastmonth"),
+            Range::ThisYear => write!(f, "thisyear"),
+            Range::LastYear => write!(f, "lastyear"),
+            Range::LastDays(days) => write!(f, "last{}days", days),
+            Range::Year(year) => write!(f, "{:04}", year),
+            Range::Month(month) => write!(f, "{:04}/{:02}", month.year(), month.month()),
+            Range::Week(week) => write!(f, "{:04}/w{:02}", week.year(), week.week()),
+            Range::Day(date) => write!(f, "{}", format_date(date)),
+            Range::FromTo(from, until) => {
+                write!(f, "{}-{}", format_date(from), format_date(until))
+            }
+            Range::Since(from) => write!(f, "{}-", format_date(from)),
+            Range::Until(until) => write!(f, "-{}", format_date(until)),
+        }
+    }
+}
+
+// Parses a single calendar descriptor: `YYYY`, `YYYY/MM`, `YYYY/wNN` or `YYYY/MM/DD`.
+fn parse_calendar(s: &str) -> Result<Range, ParseRangeError> {
+    let invalid = || ParseRangeError(s.to_string());
+    let number = |part: &str| -> Result<u32, ParseRangeError> {
+        match part.chars().all(|c| c.is_ascii_digit()) && !part.is_empty() {
+            true => part.parse().map_err(|_| invalid()),
+            false => Err(invalid()),
         }
     };
-    (from, until, _in)
+    let parts: Vec<&str> = s.split('/').collect();
+    let year = i32::try_from(number(parts[0])?).map_err(|_| invalid())?;
+    match parts[1..] {
+        [] => Ok(Range::Year(year)),
+        [week] if week.starts_with('w') => {
+            Range::week(year, number(&week[1..])?).ok_or_else(invalid)
+        }
+        [month] => Range::month(year, number(month)?).ok_or_else(invalid),
+        [month, day] => Range::day(year, number(month)?, number(day)?).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+impl FromStr for Range {
+    type Err = ParseRangeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alltime" => return Ok(Range::AllTime),
+            "today" => return Ok(Range::Today),
+            "yesterday" => return Ok(Range::Yesterday),
+            "thisweek" => return Ok(Range::ThisWeek),
+            "lastweek" => return Ok(Range::LastWeek),
+            "thismonth" => return Ok(Range::ThisMonth),
+            "lastmonth" => return Ok(Range::LastMonth),
+            "thisyear" => return Ok(Range::ThisYear),
+            "lastyear" => return Ok(Range::LastYear),
+            _ => {}
+        }
+        if let Some(days) = s.strip_prefix("last").and_then(|s| s.strip_suffix("days")) {
+            if !days.is_empty() && days.chars().all(|c| c.is_ascii_digit()) {
+                return days
+                    .parse()
+                    .map(Range::LastDays)
+                    .map_err(|_| ParseRangeError(s.to_string()));
+            }
+        }
+        let Some((from, until)) = s.split_once('-') else {
+            return parse_calendar(s);
+        };
+        // Either side of a range may be any calendar descriptor, covering all of its days.
+        let bound = |part: &str, first: bool| -> Result<Option<NaiveDate>, ParseRangeError> {
+            if part.is_empty() {
+                return Ok(None);
+            }
+            let bounds = parse_calendar(part)?
+                .calendar_bounds()
+                .ok_or_else(|| ParseRangeError(s.to_string()))?;
+            Ok(Some(if first { bounds.0 } else { bounds.1 }))
+        };
+        match (bound(from, true)?, bound(until, false)?) {
+            (Some(from), Some(until)) => Ok(Range::FromTo(from, until)),
+            (Some(from), None) => Ok(Range::Since(from)),
+            (None, Some(until)) => Ok(Range::Until(until)),
+            (None, None) => Err(ParseRangeError(s.to_string())),
+        }
+    }
+}
+
+/// The `from`, `until` and `in` query parameters for a `Range`.
+pub type FromUntilIn = (Option<String>, Option<String>, Option<String>);
+
+/// Turns a `Range` into the `from`, `until` and `in` query parameters maloja expects,
+/// or an error if the range can't be requested.
+pub fn process_range(range: Range) -> Result<FromUntilIn, RequestError> {
+    match range {
+        Range::AllTime => Ok((None, None, None)),
+        Range::LastDays(0) => Err(RequestError::InvalidRange(
+            "a range of the last 0 days is empty".to_string(),
+        )),
+        Range::LastDays(_) => process_range(range.resolve()),
+        Range::FromTo(from, until) if from > until => Err(RequestError::InvalidRange(format!(
+            "{} is after {}",
+            format_date(&from),
+            format_date(&until)
+        ))),
+        Range::FromTo(from, until) => {
+            Ok((Some(format_date(&from)), Some(format_date(&until)), None))
+        }
+        Range::Since(from) => Ok((Some(format_date(&from)), None, None)),
+        Range::Until(until) => Ok((None, Some(format_date(&until)), None)),
+        range => Ok((None, None, Some(range.to_string()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn months_are_equal_however_they_are_made() {
+        let january = Range::month(2024, 1).unwrap();
+        let split = Range::FromTo(date(2024, 1, 15), date(2024, 3, 10))
+            .split(Step::Month)
+            .unwrap();
+        assert_eq!(split[0], january);
+        assert_eq!(Range::ThisMonth.resolve_on(date(2024, 1, 20)), january);
+        assert_eq!(Range::LastMonth.resolve_on(date(2024, 2, 29)), january);
+        assert_eq!("2024/01".parse::<Range>().unwrap(), january);
+        assert_eq!(Range::month(2024, 2).unwrap().previous(), Some(january));
+        let range = RangeRes {
+            fromstring: Some("2024/01".to_string()),
+            tostring: Some("2024/01".to_string()),
+            fromstamp: None,
+            tostamp: None,
+            description: None,
+        };
+        assert_eq!(Range::from_rangeres(&range).unwrap(), january);
+        assert!(Range::month(2024, 13).is_none());
+        assert!(YearMonth::new(2024, 0).is_none());
+        let Range::Month(month) = january else {
+            panic!("{:?} is not a month", january);
+        };
+        assert_eq!((month.year(), month.month()), (2024, 1));
+        assert_eq!(
+            january.bounds(),
+            (Some(date(2024, 1, 1)), Some(date(2024, 1, 31)))
+        );
+    }
+
+    #[test]
+    fn shift_moves_by_the_range_unit() {
+        assert_eq!(Range::Year(2024).shift(-3), Some(Range::Year(2021)));
+        assert_eq!(
+            Range::month(2024, 11).unwrap().shift(3),
+            Range::month(2025, 2)
+        );
+        assert_eq!(
+            Range::month(2024, 3).unwrap().shift(-14),
+            Range::month(2023, 1)
+        );
+        assert_eq!(
+            Range::week(2024, 1).unwrap().previous(),
+            Range::week(2023, 52)
+        );
+        assert_eq!(
+            Range::day(2024, 2, 28).unwrap().shift(2),
+            Range::day(2024, 3, 1)
+        );
+        assert_eq!(
+            Range::FromTo(date(2024, 1, 1), date(2024, 1, 10)).shift(-1),
+            Some(Range::FromTo(date(2023, 12, 22), date(2023, 12, 31)))
+        );
+        assert_eq!(Range::Since(date(2024, 1, 1)).shift(1), None);
+        assert_eq!(Range::AllTime.next(), None);
+    }
+
+    #[test]
+    fn iso_week_53() {
+        let week = Range::week(2020, 53).unwrap();
+        assert_eq!(week.to_string(), "2020/w53");
+        assert_eq!("2020/w53".parse::<Range>().unwrap(), week);
+        assert!(Range::week(2021, 53).is_none());
+        assert_eq!(
+            week.bounds(),
+            (Some(date(2020, 12, 28)), Some(date(2021, 1, 3)))
+        );
+        assert_eq!(week.next(), Range::week(2021, 1));
+        assert_eq!(Range::week(2021, 1).unwrap().previous(), Some(week));
+        assert_eq!(
+            Range::FromTo(date(2020, 12, 25), date(2021, 1, 4)).split(Step::Week),
+            Some(vec![
+                Range::week(2020, 52).unwrap(),
+                week,
+                Range::week(2021, 1).unwrap(),
+            ])
+        );
+    }
+
+    #[test]
+    fn splitting_across_year_boundaries() {
+        let range = Range::FromTo(date(2023, 12, 30), date(2024, 1, 2));
+        assert_eq!(
+            range.split(Step::Day),
+            Some(vec![
+                Range::day(2023, 12, 30).unwrap(),
+                Range::day(2023, 12, 31).unwrap(),
+                Range::day(2024, 1, 1).unwrap(),
+                Range::day(2024, 1, 2).unwrap(),
+            ])
+        );
+        assert_eq!(
+            range.split(Step::Month),
+            Some(vec![
+                Range::month(2023, 12).unwrap(),
+                Range::month(2024, 1).unwrap(),
+            ])
+        );
+        assert_eq!(
+            range.split(Step::Year),
+            Some(vec![Range::Year(2023), Range::Year(2024)])
+        );
+    }
+
+    #[test]
+    fn steps_of_several_units() {
+        let range = Range::FromTo(date(2024, 1, 15), date(2024, 5, 3));
+        assert_eq!(
+            range.steps(Step::Month, 2),
+            Some(vec![
+                Range::FromTo(date(2024, 1, 1), date(2024, 2, 29)),
+                Range::FromTo(date(2024, 3, 1), date(2024, 4, 30)),
+                Range::FromTo(date(2024, 5, 1), date(2024, 6, 30)),
+            ])
+        );
+        assert_eq!(
+            Range::Year(2024).steps(Step::Week, 26),
+            Some(vec![
+                Range::FromTo(date(2024, 1, 1), date(2024, 6, 30)),
+                Range::FromTo(date(2024, 7, 1), date(2024, 12, 29)),
+                Range::FromTo(date(2024, 12, 30), date(2025, 6, 29)),
+            ])
+        );
+        assert_eq!(range.steps(Step::Day, 0), None);
+        assert_eq!(Range::Until(date(2024, 1, 1)).steps(Step::Day, 1), None);
+    }
 }
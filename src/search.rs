@@ -0,0 +1,213 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::{block_on, full_query_path, handle_response, MalojaCredentials};
+use crate::{json::*, types::*};
+use reqwest::Client;
+
+/// Artists, tracks, and albums matching a search, best matches first.
+///
+/// Artists and albums whose ID can't be found in the response are left out.
+#[derive(Debug, Clone, Default)]
+pub struct SearchResults {
+    /// Matching artists.
+    pub artists: Vec<Artist>,
+    /// Matching tracks.
+    pub tracks: Vec<Track>,
+    /// Matching albums.
+    pub albums: Vec<Album>,
+}
+
+// Not every maloja version reports IDs in search results directly,
+// but the links it gives do contain them, e.g. `/image?artist_id=12`.
+fn find_id(id: Option<u64>, links: [&Option<String>; 2], key: &str) -> Option<String> {
+    if let Some(id) = id {
+        return Some(id.to_string());
+    }
+    let pattern = key.to_owned() + "=";
+    links.into_iter().flatten().find_map(|link| {
+        let (_, query) = link.split_once('?')?;
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(&pattern))
+            .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+            .map(|id| id.to_string())
+    })
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::search].
+    pub async fn search_async(
+        &self,
+        query: String,
+        max_results: Option<u64>,
+    ) -> Result<SearchResults, RequestError> {
+        let requestbody = SearchReq {
+            query,
+            max: max_results,
+        };
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/search")?)
+            .send()
+            .await;
+        match handle_response::<SearchRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => Ok(SearchResults {
+                artists: response
+                    .artists
+                    .into_iter()
+                    .filter_map(|result| {
+                        Some(Artist {
+                            id: find_id(
+                                result.artist_id,
+                                [&result.image, &result.link],
+                                "artist_id",
+                            )?,
+                            name: result.artist,
+                        })
+                    })
+                    .collect(),
+                tracks: response
+                    .tracks
+                    .into_iter()
+                    .map(|result| {
+                        let id =
+                            find_id(result.track_id, [&result.image, &result.link], "track_id");
+                        Track::from_trackres(result.track, id)
+                    })
+                    .collect(),
+                albums: response
+                    .albums
+                    .into_iter()
+                    .filter_map(|result| {
+                        Some(Album {
+                            id: find_id(
+                                result.album_id,
+                                [&result.image, &result.link],
+                                "album_id",
+                            )?,
+                            name: result.album.albumtitle,
+                            artists: result.album.artists,
+                        })
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Searches for artists, tracks, and albums by name. See [search].
+    pub fn search(
+        &self,
+        query: String,
+        max_results: Option<u64>,
+    ) -> Result<SearchResults, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.search_async(query, max_results).await })
+    }
+}
+
+/// See [search].
+pub async fn search_async(
+    query: String,
+    max_results: Option<u64>,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<SearchResults, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .search_async(query, max_results)
+        .await
+}
+
+/// Searches for artists, tracks, and albums by name, for example to find the IDs
+/// [crate::art::album_art] and [crate::art::artist_art] need.
+///
+/// An artist's or album's ID is left empty if the server didn't report it.
+///
+/// # Arguments
+///
+/// * `query` - The text to search for.
+/// * `max_results` - Optionally, the most results to return of each kind.
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let results = mljcl::search::search("Some album".to_string(), Some(5), creds.clone()).unwrap();
+/// if let Some(album) = results.albums.first() {
+///     let art = mljcl::art::album_art(album.id.clone(), creds).unwrap();
+/// }
+/// ```
+pub fn search(
+    query: String,
+    max_results: Option<u64>,
+    credentials: MalojaCredentials,
+) -> Result<SearchResults, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .search(query, max_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    #[test]
+    fn ids_are_found_in_links() {
+        let link = Some("/artist?artist_id=12".to_string());
+        let image = Some("/image?album_id=&x=1".to_string());
+        assert_eq!(
+            find_id(Some(3), [&None, &link], "artist_id"),
+            Some("3".to_string())
+        );
+        assert_eq!(
+            find_id(None, [&None, &link], "artist_id"),
+            Some("12".to_string())
+        );
+        assert_eq!(find_id(None, [&image, &None], "album_id"), None);
+        let image = Some("/image?x=1&album_id=5".to_string());
+        assert_eq!(
+            find_id(None, [&image, &link], "album_id"),
+            Some("5".to_string())
+        );
+        assert_eq!(find_id(None, [&None, &None], "track_id"), None);
+    }
+
+    #[test]
+    fn results_without_an_id_are_left_out() {
+        let (credentials, requests) = serve(vec![(
+            200,
+            r#"{
+                "artists":[
+                    {"artist":"A","artist_id":1},
+                    {"artist":"B","link":"/artist?artist_id=2"},
+                    {"artist":"C","link":"/artist?artist=C"}
+                ],
+                "tracks":[{"track":{"artists":["A"],"title":"T"}}],
+                "albums":[
+                    {"album":{"artists":["A"],"albumtitle":"X"},"image":"/image?album_id=4"},
+                    {"album":{"artists":["A"],"albumtitle":"Y"}}
+                ]
+            }"#,
+        )]);
+        let results = MalojaClient::new(credentials)
+            .unwrap()
+            .search("a".to_string(), Some(5))
+            .unwrap();
+        let artists: Vec<(&str, &str)> = results
+            .artists
+            .iter()
+            .map(|artist| (artist.name.as_str(), artist.id.as_str()))
+            .collect();
+        assert_eq!(artists, [("A", "1"), ("B", "2")]);
+        assert_eq!(results.tracks.len(), 1);
+        assert_eq!(results.tracks[0].id, None);
+        let albums: Vec<(&str, &str)> = results
+            .albums
+            .iter()
+            .map(|album| (album.name.as_str(), album.id.as_str()))
+            .collect();
+        assert_eq!(albums, [("X", "4")]);
+        assert!(requests.recv().unwrap().contains("max=5"));
+    }
+}
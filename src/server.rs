@@ -0,0 +1,325 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::json::ServerInfoRes;
+use crate::{block_on, handle_response, MalojaCredentials};
+use reqwest::Client;
+use std::fmt;
+
+/// A maloja version number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// The major version.
+    pub major: u32,
+    /// The minor version.
+    pub minor: u32,
+    /// The patch version.
+    pub patch: u32,
+}
+
+impl Version {
+    /// Creates a version from its components.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses a version like `"3.2.2"`. Missing components count as 0, and anything
+    /// after a component's leading digits (e.g. `"-dev"`) is ignored.
+    pub fn parse(version: &str) -> Option<Self> {
+        Version::from_components(version.trim().trim_start_matches('v').split('.'))
+    }
+
+    fn from_components<'a>(components: impl Iterator<Item = &'a str>) -> Option<Self> {
+        let mut numbers = components.map(|component| {
+            let digits: String = component
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            digits.parse::<u32>().ok()
+        });
+        Some(Version {
+            major: numbers.next()??,
+            minor: numbers.next().flatten().unwrap_or(0),
+            patch: numbers.next().flatten().unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A feature which only some maloja versions support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Albums: album charts, album info, album art, and filtering by album.
+    Albums,
+}
+
+impl Capability {
+    /// The first maloja version supporting this feature.
+    pub fn min_version(&self) -> Version {
+        match self {
+            Capability::Albums => Version::new(3, 2, 0),
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Albums => write!(f, "albums"),
+        }
+    }
+}
+
+/// The state of a maloja server's database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DbStatus {
+    /// Whether the database is working.
+    pub healthy: bool,
+    /// Whether the database is being rebuilt, e.g. after an upgrade.
+    pub rebuild_in_progress: bool,
+    /// Whether the database has finished loading.
+    pub complete: bool,
+}
+
+/// Information about a maloja server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// The name the server was given in its settings.
+    pub name: String,
+    /// The maloja version the server is running.
+    pub version: Version,
+    /// The state of the server's database.
+    pub db_status: DbStatus,
+}
+
+impl ServerInfo {
+    /// Whether the server's version supports a feature.
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.version >= capability.min_version()
+    }
+
+    #[allow(missing_docs)]
+    pub fn from_serverinfores(info: ServerInfoRes) -> Result<Self, RequestError> {
+        let version = info
+            .versionstring
+            .as_deref()
+            .and_then(Version::parse)
+            .or_else(|| {
+                let components: Vec<String> = info
+                    .version
+                    .as_ref()?
+                    .as_array()?
+                    .iter()
+                    .map(|component| match component {
+                        serde_json::Value::String(component) => component.clone(),
+                        component => component.to_string(),
+                    })
+                    .collect();
+                Version::from_components(components.iter().map(String::as_str))
+            })
+            .ok_or_else(|| {
+                RequestError::MalformedResponse("response did not include a version".to_string())
+            })?;
+        let db_status = info
+            .db_status
+            .map_or_else(DbStatus::default, |status| DbStatus {
+                healthy: status.healthy.unwrap_or(false),
+                rebuild_in_progress: status.rebuildinprogress.unwrap_or(false),
+                complete: status.complete.unwrap_or(false),
+            });
+        Ok(ServerInfo {
+            name: info.name.unwrap_or_default(),
+            version,
+            db_status,
+        })
+    }
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::server_info].
+    pub async fn server_info_async(&self) -> Result<ServerInfo, RequestError> {
+        let response = self.get("/apis/mlj_1/serverinfo").send().await;
+        let info =
+            ServerInfo::from_serverinfores(handle_response::<ServerInfoRes>(response).await?)?;
+        self.cache_server_info(info.clone());
+        Ok(info)
+    }
+
+    /// Fetches the server's name, version and database status.
+    ///
+    /// The client remembers the result, and uses the version to report features the server
+    /// doesn't support as [RequestError::Unsupported].
+    pub fn server_info(&self) -> Result<ServerInfo, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.server_info_async().await })
+    }
+
+    /// See [MalojaClient::supports].
+    pub async fn supports_async(&self, capability: Capability) -> Result<bool, RequestError> {
+        match self.cached_server_info() {
+            Some(info) => Ok(info.supports(capability)),
+            None => Ok(self.server_info_async().await?.supports(capability)),
+        }
+    }
+
+    /// Whether the server supports a feature, fetching its version first if it isn't known yet.
+    pub fn supports(&self, capability: Capability) -> Result<bool, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.supports_async(capability).await })
+    }
+
+    // Runs a request for a feature only some servers support. If the server is already known to
+    // be too old, the request isn't sent. Otherwise, if the request fails, the server's version
+    // is checked so that the failure can be reported as unsupported rather than e.g. a decode error.
+    #[cfg(feature = "full")]
+    pub(crate) async fn gated<T>(
+        &self,
+        capability: Capability,
+        request: impl std::future::Future<Output = Result<T, RequestError>>,
+    ) -> Result<T, RequestError> {
+        let unsupported = |info: &ServerInfo| RequestError::Unsupported {
+            capability,
+            version: info.version,
+        };
+        if let Some(info) = self.cached_server_info() {
+            if !info.supports(capability) {
+                return Err(unsupported(&info));
+            }
+        }
+        match request.await {
+            Err(
+                error @ (RequestError::StatusError { .. }
+                | RequestError::ServerError { .. }
+                | RequestError::DecodeError { .. }
+                | RequestError::MalformedResponse(_)),
+            ) if self.cached_server_info().is_none() => match self.server_info_async().await {
+                Ok(info) if !info.supports(capability) => Err(unsupported(&info)),
+                _ => Err(error),
+            },
+            result => result,
+        }
+    }
+}
+
+/// See [server_info].
+pub async fn server_info_async(
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<ServerInfo, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .server_info_async()
+        .await
+}
+
+/// Fetches a server's name, version and database status.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::server::Capability;
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let info = mljcl::server::server_info(creds).unwrap();
+/// println!("{} runs maloja {}", info.name, info.version);
+/// if !info.supports(Capability::Albums) {
+///     println!("Album charts aren't available on this server.");
+/// }
+/// ```
+pub fn server_info(credentials: MalojaCredentials) -> Result<ServerInfo, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .server_info()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_parsed() {
+        assert_eq!(Version::parse("3.2.0"), Some(Version::new(3, 2, 0)));
+        assert_eq!(Version::parse("3.2"), Some(Version::new(3, 2, 0)));
+        assert_eq!(Version::parse("v3.2.0-dev"), Some(Version::new(3, 2, 0)));
+        assert_eq!(Version::parse("dev"), None);
+        let info: ServerInfoRes =
+            serde_json::from_str(r#"{"version":[3,"2","1-dev"],"versionstring":null}"#).unwrap();
+        let info = ServerInfo::from_serverinfores(info).unwrap();
+        assert_eq!(info.version, Version::new(3, 2, 1));
+        assert!(info.supports(Capability::Albums));
+        let info: ServerInfoRes = serde_json::from_str(r#"{"version":["x"]}"#).unwrap();
+        assert!(matches!(
+            ServerInfo::from_serverinfores(info),
+            Err(RequestError::MalformedResponse(_))
+        ));
+    }
+
+    // Gating is only used by the requests behind the `full` feature.
+    #[cfg(feature = "full")]
+    mod gated {
+        use super::*;
+        use crate::testing::serve;
+
+        const OLD_SERVER: &str = r#"{"name":"Old","version":[3,1,4],"versionstring":"3.1.4"}"#;
+        const NEW_SERVER: &str =
+            r#"{"name":"New","version":["3","2","2"],"versionstring":"3.2.2"}"#;
+
+        fn gated_request(
+            client: &MalojaClient,
+            result: Result<u32, RequestError>,
+        ) -> Result<u32, RequestError> {
+            let client = client.clone();
+            block_on(async move { client.gated(Capability::Albums, async { result }).await })
+        }
+
+        #[test]
+        fn old_servers_are_unsupported() {
+            // Only the server info is served, so the cached version must stop any second request.
+            let client = MalojaClient::new(serve(vec![(200, OLD_SERVER)]).0).unwrap();
+            assert!(!client.supports(Capability::Albums).unwrap());
+            assert!(!client.supports(Capability::Albums).unwrap());
+            assert!(matches!(
+                gated_request(&client, Ok(1)),
+                Err(RequestError::Unsupported {
+                    capability: Capability::Albums,
+                    version,
+                }) if version == Version::new(3, 1, 4)
+            ));
+        }
+
+        #[test]
+        fn failed_requests_check_the_version() {
+            let (credentials, requests) = serve(vec![(200, OLD_SERVER)]);
+            let client = MalojaClient::new(credentials).unwrap();
+            let failed = RequestError::MalformedResponse("no albums".to_string());
+            assert!(matches!(
+                gated_request(&client, Err(failed)),
+                Err(RequestError::Unsupported { .. })
+            ));
+            assert!(requests.recv().unwrap().contains("/apis/mlj_1/serverinfo"));
+            assert_eq!(client.cached_server_info().unwrap().name, "Old");
+        }
+
+        #[test]
+        fn new_servers_run_the_request() {
+            let client = MalojaClient::new(serve(vec![(200, NEW_SERVER)]).0).unwrap();
+            assert_eq!(gated_request(&client, Ok(1)).unwrap(), 1);
+            // Successful requests don't need the version.
+            assert!(client.cached_server_info().is_none());
+            client.server_info().unwrap();
+            assert_eq!(gated_request(&client, Ok(2)).unwrap(), 2);
+            let failed = RequestError::MalformedResponse("no albums".to_string());
+            assert!(matches!(
+                gated_request(&client, Err(failed)),
+                Err(RequestError::MalformedResponse(_))
+            ));
+        }
+    }
+}
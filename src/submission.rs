@@ -0,0 +1,266 @@
+use crate::errors::ScrobbleSubmissionBuilderError;
+use crate::json::{
+    ListenAdditionalInfoReq, ListenReq, ListenTrackMetadataReq, ListenType, ScrobbleReq,
+};
+use chrono::{DateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A scrobble to be submitted to a maloja server, with all of the metadata maloja accepts.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ScrobbleSubmission {
+    /// The title of the track.
+    pub title: String,
+    /// The track's artists.
+    pub artists: Vec<String>,
+    /// Optionally, the album the track is in.
+    pub album: Option<String>,
+    /// Optionally, the album's artists.
+    pub album_artists: Option<Vec<String>>,
+    /// Optionally, when the track was played. The server uses the time it received the scrobble otherwise.
+    pub time: Option<DateTime<Utc>>,
+    /// Optionally, how long the track was listened to.
+    pub duration: Option<Duration>,
+    /// Optionally, the full length of the track.
+    pub length: Option<Duration>,
+}
+
+impl ScrobbleSubmission {
+    /// Creates a builder for `ScrobbleSubmission`.
+    pub fn builder() -> ScrobbleSubmissionBuilder {
+        ScrobbleSubmissionBuilder::default()
+    }
+
+    pub(crate) fn into_scrobblereq(self, key: String) -> ScrobbleReq {
+        ScrobbleReq {
+            artist: None,
+            artists: Some(self.artists),
+            title: self.title,
+            album: self.album,
+            albumartists: self.album_artists,
+            duration: self.duration.map(|duration| duration.as_secs()),
+            length: self.length.map(|length| length.as_secs()),
+            time: self.time.map(|time| time.timestamp()),
+            key,
+        }
+    }
+
+    // Audioscrobbler takes a form rather than JSON. Batched scrobbles have their index in each
+    // parameter's name, and now playing notifications have no timestamp.
+    pub(crate) fn into_audioscrobbler_params(
+        self,
+        index: Option<usize>,
+        with_timestamp: bool,
+    ) -> Vec<(String, String)> {
+        let name = |name: &str| match index {
+            Some(index) => format!("{}[{}]", name, index),
+            None => name.to_string(),
+        };
+        let mut params = vec![
+            (name("artist"), self.artists.join(", ")),
+            (name("track"), self.title),
+        ];
+        if with_timestamp {
+            let time = self.time.unwrap_or_else(Utc::now);
+            params.push((name("timestamp"), time.timestamp().to_string()));
+        }
+        if let Some(album) = self.album {
+            params.push((name("album"), album));
+        }
+        if let Some(album_artists) = self.album_artists {
+            params.push((name("albumArtist"), album_artists.join(", ")));
+        }
+        if let Some(length) = self.length {
+            params.push((name("duration"), length.as_secs().to_string()));
+        }
+        params
+    }
+
+    // The 1.2 protocol names its parameters with single letters, always followed by the scrobble's index.
+    // Fields the protocol requires but mljcl doesn't know, such as the rating, are sent empty.
+    pub(crate) fn into_legacy_params(self, index: usize) -> Vec<(String, String)> {
+        let time = self.time.unwrap_or_else(Utc::now);
+        // P (chosen by the user) requires the length, so without one the source is U (unknown).
+        let (source, length) = match self.length {
+            Some(length) => ("P", length.as_secs().to_string()),
+            None => ("U", String::new()),
+        };
+        vec![
+            (format!("a[{}]", index), self.artists.join(", ")),
+            (format!("t[{}]", index), self.title),
+            (format!("i[{}]", index), time.timestamp().to_string()),
+            (format!("o[{}]", index), source.to_string()),
+            (format!("r[{}]", index), String::new()),
+            (format!("l[{}]", index), length),
+            (format!("b[{}]", index), self.album.unwrap_or_default()),
+            (format!("n[{}]", index), String::new()),
+            (format!("m[{}]", index), String::new()),
+        ]
+    }
+
+    pub(crate) fn into_listenreq(self, listen_type: ListenType) -> ListenReq {
+        let listened_at = match listen_type {
+            ListenType::PlayingNow => None,
+            // ListenBrainz requires a time for finished listens, even though maloja doesn't.
+            ListenType::Single | ListenType::Import => {
+                Some(self.time.unwrap_or_else(Utc::now).timestamp())
+            }
+        };
+        // ListenBrainz has no field for how long the track was listened to, so `duration` is dropped.
+        ListenReq {
+            listened_at,
+            track_metadata: ListenTrackMetadataReq {
+                artist_name: self.artists.join(", "),
+                track_name: self.title,
+                release_name: self.album,
+                additional_info: ListenAdditionalInfoReq {
+                    artist_names: self.artists,
+                    release_artist_names: self.album_artists,
+                    duration: self.length.map(|length| length.as_secs()),
+                    duration_ms: self.length.map(|length| length.as_millis()),
+                },
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+/// A builder for `ScrobbleSubmission`, which needs at least a title and one artist.
+pub struct ScrobbleSubmissionBuilder {
+    title: Option<String>,
+    artists: Vec<String>,
+    album: Option<String>,
+    album_artists: Option<Vec<String>>,
+    time: Option<DateTime<Utc>>,
+    duration: Option<Duration>,
+    length: Option<Duration>,
+}
+
+impl ScrobbleSubmissionBuilder {
+    /// Initializes a blank `ScrobbleSubmissionBuilder`.
+    pub fn new() -> ScrobbleSubmissionBuilder {
+        ScrobbleSubmissionBuilder::default()
+    }
+
+    /// Set the track title.
+    pub fn title(mut self, title: String) -> ScrobbleSubmissionBuilder {
+        self.title = Some(title);
+        self
+    }
+
+    /// Add an artist to the track.
+    pub fn artist(mut self, artist: String) -> ScrobbleSubmissionBuilder {
+        self.artists.push(artist);
+        self
+    }
+
+    /// Set all of the track's artists, replacing any added before.
+    pub fn artists(mut self, artists: Vec<String>) -> ScrobbleSubmissionBuilder {
+        self.artists = artists;
+        self
+    }
+
+    /// Set the album the track is in.
+    pub fn album(mut self, album: String) -> ScrobbleSubmissionBuilder {
+        self.album = Some(album);
+        self
+    }
+
+    /// Set the album's artists.
+    pub fn album_artists(mut self, album_artists: Vec<String>) -> ScrobbleSubmissionBuilder {
+        self.album_artists = Some(album_artists);
+        self
+    }
+
+    /// Set when the track was played, for backfilling past plays. Must not be before 1970.
+    pub fn time(mut self, time: DateTime<Utc>) -> ScrobbleSubmissionBuilder {
+        self.time = Some(time);
+        self
+    }
+
+    /// Set how long the track was listened to.
+    pub fn duration(mut self, duration: Duration) -> ScrobbleSubmissionBuilder {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set the full length of the track.
+    pub fn length(mut self, length: Duration) -> ScrobbleSubmissionBuilder {
+        self.length = Some(length);
+        self
+    }
+
+    /// Builds the submission, returning either the submission or an error.
+    pub fn build(self) -> Result<ScrobbleSubmission, ScrobbleSubmissionBuilderError> {
+        let title = self
+            .title
+            .ok_or(ScrobbleSubmissionBuilderError::MissingTitle)?;
+        if self.artists.is_empty() {
+            return Err(ScrobbleSubmissionBuilderError::MissingArtist);
+        }
+        if self.time.is_some_and(|time| time.timestamp() < 0) {
+            return Err(ScrobbleSubmissionBuilderError::TimeBeforeEpoch);
+        }
+        Ok(ScrobbleSubmission {
+            title,
+            artists: self.artists,
+            album: self.album,
+            album_artists: self.album_artists,
+            time: self.time,
+            duration: self.duration,
+            length: self.length,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listens_send_length_but_not_duration() {
+        let time = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let submission = ScrobbleSubmission::builder()
+            .title("Track".to_string())
+            .artists(vec!["A".to_string(), "B".to_string()])
+            .time(time)
+            .duration(Duration::from_secs(30))
+            .length(Duration::from_millis(200_500))
+            .build()
+            .unwrap();
+        let listen = submission.clone().into_listenreq(ListenType::Single);
+        assert_eq!(listen.listened_at, Some(1_700_000_000));
+        assert_eq!(listen.track_metadata.artist_name, "A, B");
+        let info = serde_json::to_value(&listen.track_metadata.additional_info).unwrap();
+        assert_eq!(
+            info,
+            serde_json::json!({
+                "artist_names": ["A", "B"],
+                "duration": 200,
+                "duration_ms": 200_500,
+            })
+        );
+        let playing = submission.into_listenreq(ListenType::PlayingNow);
+        assert_eq!(playing.listened_at, None);
+    }
+
+    #[test]
+    fn times_before_the_epoch_are_rejected() {
+        let builder = ScrobbleSubmission::builder()
+            .title("Track".to_string())
+            .artist("Artist".to_string());
+        assert!(matches!(
+            builder
+                .time(DateTime::from_timestamp(-1, 0).unwrap())
+                .build(),
+            Err(ScrobbleSubmissionBuilderError::TimeBeforeEpoch)
+        ));
+        let epoch = ScrobbleSubmission::builder()
+            .title("Track".to_string())
+            .artist("Artist".to_string())
+            .time(DateTime::from_timestamp(0, 0).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(epoch.into_scrobblereq("key".to_string()).time, Some(0));
+    }
+}
@@ -0,0 +1,402 @@
+use crate::block_on;
+use crate::client::MalojaClient;
+use crate::credentials::MalojaCredentials;
+use crate::errors::RequestError;
+use crate::history::{Scrobble, ScrobbleQuery};
+use crate::range::Range;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use reqwest::Client;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// How far a sync has gotten, to be stored between syncs and passed to the next one.
+///
+/// An empty (default) checkpoint syncs the whole history.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Checkpoint {
+    /// When the most recent scrobble seen was played. This never moves backwards, even if that
+    /// scrobble is deleted, so that the window doesn't grow to cover scrobbles never compared.
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Hashes of the scrobbles within the look-back window before `last_seen`,
+    /// by the second they were played at.
+    pub window: BTreeMap<i64, u64>,
+}
+
+/// How to sync scrobbles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SyncOptions {
+    /// How far before the checkpoint to check for scrobbles which were edited or deleted.
+    pub look_back: Duration,
+    /// How many scrobbles to fetch per request.
+    pub page_size: u64,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions {
+            look_back: Duration::from_secs(7 * 24 * 60 * 60),
+            page_size: 500,
+        }
+    }
+}
+
+/// The outcome of [sync_scrobbles].
+#[derive(Clone, Debug, Default)]
+pub struct SyncReport {
+    /// Scrobbles which weren't seen before, oldest first.
+    pub new: Vec<Scrobble>,
+    /// Scrobbles within the look-back window which have changed since the last sync, oldest first.
+    pub changed: Vec<Scrobble>,
+    /// When the scrobbles within the look-back window which have been deleted since the last sync were played.
+    pub deleted: Vec<DateTime<Utc>>,
+    /// The checkpoint to pass to the next sync.
+    pub checkpoint: Checkpoint,
+}
+
+// FNV-1a, written out rather than using `std::hash`, since the hashes are persisted and need
+// to stay the same across Rust versions.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn scrobble_hash(scrobble: &Scrobble) -> u64 {
+    let mut hash = fnv1a(
+        0xcbf2_9ce4_8422_2325,
+        &scrobble.time.timestamp().to_le_bytes(),
+    );
+    let track = &scrobble.track;
+    let fields = [
+        vec![track.name.as_str()],
+        track.artists.iter().map(String::as_str).collect(),
+        track.album.iter().map(String::as_str).collect(),
+        track
+            .album_artists
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect(),
+    ];
+    // Strings end with a 0 byte and fields with a 1 byte, so moving text between them changes the hash.
+    for field in fields {
+        for value in field {
+            hash = fnv1a(hash, value.as_bytes());
+            hash = fnv1a(hash, &[0]);
+        }
+        hash = fnv1a(hash, &[1]);
+    }
+    hash
+}
+
+// The start of the look-back window before `last_seen`, or `None` if the whole history is in it.
+fn window_start(
+    last_seen: Option<DateTime<Utc>>,
+    look_back: chrono::Duration,
+) -> Option<DateTime<Utc>> {
+    last_seen.and_then(|last_seen| last_seen.checked_sub_signed(look_back))
+}
+
+// Compares the scrobbles fetched since the start of the checkpoint's window, oldest first,
+// with the hashes in the checkpoint.
+fn diff_scrobbles(
+    checkpoint: &Checkpoint,
+    fetched: Vec<Scrobble>,
+    look_back: chrono::Duration,
+) -> SyncReport {
+    let from = window_start(checkpoint.last_seen, look_back);
+    let mut report = SyncReport::default();
+    let mut seen = BTreeMap::new();
+    for scrobble in fetched {
+        let time = scrobble.time.timestamp();
+        let hash = scrobble_hash(&scrobble);
+        seen.insert(time, hash);
+        match checkpoint.window.get(&time) {
+            // Besides scrobbles after the checkpoint, this catches ones added with
+            // an earlier time, e.g. when importing.
+            None => report.new.push(scrobble),
+            Some(previous) if *previous != hash => report.changed.push(scrobble),
+            Some(_) => {}
+        }
+    }
+    for time in checkpoint.window.keys() {
+        if from.is_some_and(|from| *time >= from.timestamp()) && !seen.contains_key(time) {
+            report.deleted.extend(DateTime::from_timestamp(*time, 0));
+        }
+    }
+
+    let newest = seen
+        .keys()
+        .next_back()
+        .and_then(|time| DateTime::from_timestamp(*time, 0));
+    let last_seen = newest.max(checkpoint.last_seen);
+    report.checkpoint = Checkpoint {
+        last_seen,
+        window: prune_window(seen, window_start(last_seen, look_back)),
+    };
+    report
+}
+
+// Drops the hashes of scrobbles played before the new window starts.
+fn prune_window(
+    mut window: BTreeMap<i64, u64>,
+    start: Option<DateTime<Utc>>,
+) -> BTreeMap<i64, u64> {
+    match start {
+        None => window,
+        Some(start) => window.split_off(&start.timestamp()),
+    }
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::sync_scrobbles].
+    pub async fn sync_scrobbles_async(
+        &self,
+        checkpoint: &Checkpoint,
+        options: SyncOptions,
+    ) -> Result<SyncReport, RequestError> {
+        let look_back = chrono::Duration::from_std(options.look_back)
+            .map_err(|_| RequestError::InvalidRange("look-back window is too long".to_string()))?;
+        let from = window_start(checkpoint.last_seen, look_back);
+        // The server filters by day in its own timezone, so the range starts a day early
+        // and the exact time is checked below.
+        let range = match from.and_then(|from| from.date_naive().pred_opt()) {
+            None => Range::AllTime,
+            Some(day) => Range::Since(day),
+        };
+        let mut scrobbles = self.scrobble_stream(ScrobbleQuery {
+            page_size: options.page_size,
+            ..ScrobbleQuery::new(range)
+        });
+        let mut fetched = vec![];
+        while let Some(scrobble) = scrobbles.next().await {
+            let scrobble = scrobble?;
+            if from.is_some_and(|from| scrobble.time < from) {
+                break;
+            }
+            fetched.push(scrobble);
+        }
+        fetched.reverse();
+        Ok(diff_scrobbles(checkpoint, fetched, look_back))
+    }
+
+    /// Fetches the scrobbles added, edited or deleted since a checkpoint. See [sync_scrobbles].
+    pub fn sync_scrobbles(
+        &self,
+        checkpoint: &Checkpoint,
+        options: SyncOptions,
+    ) -> Result<SyncReport, RequestError> {
+        let client = self.clone();
+        let checkpoint = checkpoint.clone();
+        block_on(async move { client.sync_scrobbles_async(&checkpoint, options).await })
+    }
+}
+
+/// See [sync_scrobbles].
+pub async fn sync_scrobbles_async(
+    checkpoint: &Checkpoint,
+    options: SyncOptions,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<SyncReport, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .sync_scrobbles_async(checkpoint, options)
+        .await
+}
+
+/// Fetches the scrobbles added, edited or deleted since a checkpoint, along with a new checkpoint.
+///
+/// Only scrobbles newer than the checkpoint's look-back window are downloaded. Within the window,
+/// scrobbles are compared with the hashes in the checkpoint to find edits and deletions. Changes
+/// to scrobbles older than the window aren't noticed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::sync::{Checkpoint, SyncOptions};
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// let checkpoint: Checkpoint = std::fs::read_to_string("checkpoint.json")
+///     .map(|json| serde_json::from_str(&json).unwrap())
+///     .unwrap_or_default();
+/// let report = mljcl::sync::sync_scrobbles(&checkpoint, SyncOptions::default(), creds).unwrap();
+/// println!(
+///     "{} new, {} changed, {} deleted",
+///     report.new.len(),
+///     report.changed.len(),
+///     report.deleted.len()
+/// );
+/// std::fs::write("checkpoint.json", serde_json::to_string(&report.checkpoint).unwrap()).unwrap();
+/// ```
+pub fn sync_scrobbles(
+    checkpoint: &Checkpoint,
+    options: SyncOptions,
+    credentials: MalojaCredentials,
+) -> Result<SyncReport, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .sync_scrobbles(checkpoint, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Track;
+
+    const HOUR: i64 = 60 * 60;
+
+    fn scrobble(time: i64, title: &str) -> Scrobble {
+        Scrobble {
+            time: DateTime::from_timestamp(time, 0).unwrap(),
+            track: Track::new(
+                title.to_string(),
+                None,
+                None,
+                None,
+                vec!["Artist".to_string()],
+            ),
+        }
+    }
+
+    fn times(scrobbles: &[Scrobble]) -> Vec<i64> {
+        scrobbles
+            .iter()
+            .map(|scrobble| scrobble.time.timestamp())
+            .collect()
+    }
+
+    // What the server returns for a sync from `checkpoint`: every scrobble from the start of its window.
+    fn fetch(history: &[Scrobble], checkpoint: &Checkpoint) -> Vec<Scrobble> {
+        let from = window_start(checkpoint.last_seen, chrono::Duration::hours(1));
+        history
+            .iter()
+            .filter(|scrobble| from.is_none_or(|from| scrobble.time >= from))
+            .cloned()
+            .collect()
+    }
+
+    fn sync(history: &[Scrobble], checkpoint: &Checkpoint) -> SyncReport {
+        diff_scrobbles(
+            checkpoint,
+            fetch(history, checkpoint),
+            chrono::Duration::hours(1),
+        )
+    }
+
+    fn history() -> Vec<Scrobble> {
+        vec![
+            scrobble(0, "Old"),
+            scrobble(3 * HOUR, "A"),
+            scrobble(3 * HOUR + 600, "B"),
+            scrobble(4 * HOUR, "C"),
+        ]
+    }
+
+    #[test]
+    fn first_sync_is_all_new() {
+        let report = sync(&history(), &Checkpoint::default());
+        assert_eq!(times(&report.new), [0, 3 * HOUR, 3 * HOUR + 600, 4 * HOUR]);
+        assert!(report.changed.is_empty() && report.deleted.is_empty());
+        let checkpoint = report.checkpoint;
+        assert_eq!(checkpoint.last_seen.unwrap().timestamp(), 4 * HOUR);
+        // The window only keeps the last hour before the newest scrobble.
+        assert_eq!(
+            checkpoint.window.keys().copied().collect::<Vec<_>>(),
+            [3 * HOUR, 3 * HOUR + 600, 4 * HOUR]
+        );
+    }
+
+    #[test]
+    fn unchanged_history_reports_nothing() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let report = sync(&history(), &checkpoint);
+        assert!(report.new.is_empty() && report.changed.is_empty() && report.deleted.is_empty());
+        assert_eq!(report.checkpoint, checkpoint);
+    }
+
+    #[test]
+    fn new_scrobbles_move_the_window() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let mut history = history();
+        history.push(scrobble(5 * HOUR, "D"));
+        let report = sync(&history, &checkpoint);
+        assert_eq!(times(&report.new), [5 * HOUR]);
+        assert_eq!(
+            report.checkpoint.window.keys().copied().collect::<Vec<_>>(),
+            [4 * HOUR, 5 * HOUR]
+        );
+    }
+
+    #[test]
+    fn edits_within_the_window_are_changes() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let mut history = history();
+        history[2] = scrobble(3 * HOUR + 600, "B (Remastered)");
+        let report = sync(&history, &checkpoint);
+        assert!(report.new.is_empty());
+        assert_eq!(times(&report.changed), [3 * HOUR + 600]);
+        assert_eq!(
+            report.checkpoint.window[&(3 * HOUR + 600)],
+            scrobble_hash(&history[2])
+        );
+    }
+
+    #[test]
+    fn deletions_within_the_window_are_reported() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let mut history = history();
+        history.remove(2);
+        let report = sync(&history, &checkpoint);
+        assert_eq!(
+            report
+                .deleted
+                .iter()
+                .map(|time| time.timestamp())
+                .collect::<Vec<_>>(),
+            [3 * HOUR + 600]
+        );
+        assert!(!report.checkpoint.window.contains_key(&(3 * HOUR + 600)));
+    }
+
+    #[test]
+    fn deletions_before_the_window_are_not_noticed() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let mut history = history();
+        history.remove(0);
+        let report = sync(&history, &checkpoint);
+        assert!(report.deleted.is_empty());
+        assert_eq!(report.checkpoint, checkpoint);
+    }
+
+    #[test]
+    fn deleting_the_newest_scrobble_keeps_the_window() {
+        // A scrobble just before the window, which moving the window back would report as new.
+        let mut history = history();
+        history.insert(1, scrobble(2 * HOUR + 1800, "Before the window"));
+        let checkpoint = sync(&history, &Checkpoint::default()).checkpoint;
+        history.pop();
+        let report = sync(&history, &checkpoint);
+        assert!(report.new.is_empty());
+        assert_eq!(report.deleted.len(), 1);
+        assert_eq!(report.checkpoint.last_seen, checkpoint.last_seen);
+        assert_eq!(
+            report.checkpoint.window.keys().copied().collect::<Vec<_>>(),
+            [3 * HOUR, 3 * HOUR + 600]
+        );
+        let report = sync(&history, &report.checkpoint);
+        assert!(report.new.is_empty() && report.changed.is_empty() && report.deleted.is_empty());
+    }
+
+    #[test]
+    fn backfilled_imports_within_the_window_are_new() {
+        let checkpoint = sync(&history(), &Checkpoint::default()).checkpoint;
+        let mut history = history();
+        history.insert(2, scrobble(3 * HOUR + 300, "Imported"));
+        history.insert(1, scrobble(HOUR, "Imported long ago"));
+        let report = sync(&history, &checkpoint);
+        assert_eq!(times(&report.new), [3 * HOUR + 300]);
+        assert!(report.checkpoint.window.contains_key(&(3 * HOUR + 300)));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::credentials::{MalojaCredentials, MalojaCredentialsBuilder};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{channel, Receiver};
+
+// Starts a server which answers one request per connection with each of `responses` in turn,
+// and sends along the text of every request it receives.
+pub(crate) fn serve(responses: Vec<(u16, &str)>) -> (MalojaCredentials, Receiver<String>) {
+    let responses: Vec<(u16, String)> = responses
+        .into_iter()
+        .map(|(status, body)| (status, body.to_string()))
+        .collect();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        for (status, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 4096];
+            loop {
+                let read = stream.read(&mut buffer).unwrap_or(0);
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map_or(0, |length| length.trim().parse().unwrap());
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+            let response = format!(
+                "HTTP/1.1 {} Status\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            // The client may hang up without reading everything, e.g. when a body is too large.
+            let _ = stream.write_all(response.as_bytes());
+            let _ = sender.send(String::from_utf8_lossy(&request).into_owned());
+        }
+    });
+    let credentials = MalojaCredentialsBuilder::new()
+        .ip("127.0.0.1".to_string())
+        .port(port)
+        .api_key("key".to_string())
+        .build()
+        .unwrap();
+    (credentials, receiver)
+}
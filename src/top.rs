@@ -0,0 +1,307 @@
+use crate::client::MalojaClient;
+use crate::errors::RequestError;
+use crate::server::Capability;
+use crate::{block_on, full_query_path, handle_response, require_field, MalojaCredentials};
+use crate::{
+    json::*,
+    range::{process_range, Range, Step},
+    types::*,
+};
+use reqwest::Client;
+
+// A top artist or album always comes with its ID, which is required rather than left empty.
+fn id_string(id: Option<u64>, name: &str) -> Result<String, RequestError> {
+    require_field(id, name).map(|id| id.to_string())
+}
+
+fn top_request(range: Range, step: Step, stepn: u32) -> Result<TopReq, RequestError> {
+    let from_until_in = process_range(range)?;
+    Ok(TopReq {
+        from: from_until_in.0,
+        until: from_until_in.1,
+        _in: from_until_in.2,
+        step: step.to_string(),
+        stepn,
+    })
+}
+
+impl MalojaClient {
+    /// See [MalojaClient::top_artists].
+    pub async fn top_artists_async(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Artist>)>, RequestError> {
+        let requestbody = top_request(range, step, stepn)?;
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/top/artists")?)
+            .send()
+            .await;
+        match handle_response::<TopArtistsRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut artists: Vec<(Range, Option<Artist>)> = vec![];
+                for entry in require_field(response.list, "list")? {
+                    artists.push((
+                        Range::from_rangeres(&entry.range)?,
+                        entry
+                            .artist
+                            .map(|name| -> Result<Artist, RequestError> {
+                                Ok(Artist {
+                                    name,
+                                    id: id_string(entry.artist_id, "artist_id")?,
+                                })
+                            })
+                            .transpose()?,
+                    ));
+                }
+                Ok(artists)
+            }
+        }
+    }
+
+    /// Fetches the most listened artist of each period in a time range. See [top_artists].
+    pub fn top_artists(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Artist>)>, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.top_artists_async(range, step, stepn).await })
+    }
+
+    /// See [MalojaClient::top_tracks].
+    pub async fn top_tracks_async(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Track>)>, RequestError> {
+        let requestbody = top_request(range, step, stepn)?;
+        let response = self
+            .get(&full_query_path(requestbody, "/apis/mlj_1/top/tracks")?)
+            .send()
+            .await;
+        match handle_response::<TopTracksRes>(response).await {
+            Err(error) => Err(error),
+            Ok(response) => {
+                let mut tracks: Vec<(Range, Option<Track>)> = vec![];
+                for entry in require_field(response.list, "list")? {
+                    tracks.push((
+                        Range::from_rangeres(&entry.range)?,
+                        entry.track.map(|track| {
+                            Track::from_trackres(track, entry.track_id.map(|id| id.to_string()))
+                        }),
+                    ));
+                }
+                Ok(tracks)
+            }
+        }
+    }
+
+    /// Fetches the most listened track of each period in a time range. See [top_tracks].
+    pub fn top_tracks(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Track>)>, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.top_tracks_async(range, step, stepn).await })
+    }
+
+    /// See [MalojaClient::top_albums].
+    pub async fn top_albums_async(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Album>)>, RequestError> {
+        self.gated(Capability::Albums, async {
+            let requestbody = top_request(range, step, stepn)?;
+            let response = self
+                .get(&full_query_path(requestbody, "/apis/mlj_1/top/albums")?)
+                .send()
+                .await;
+            match handle_response::<TopAlbumsRes>(response).await {
+                Err(error) => Err(error),
+                Ok(response) => {
+                    let mut albums: Vec<(Range, Option<Album>)> = vec![];
+                    for entry in require_field(response.list, "list")? {
+                        albums.push((
+                            Range::from_rangeres(&entry.range)?,
+                            entry
+                                .album
+                                .map(|album| -> Result<Album, RequestError> {
+                                    Ok(Album {
+                                        name: album.albumtitle,
+                                        id: id_string(entry.album_id, "album_id")?,
+                                        artists: album.artists,
+                                    })
+                                })
+                                .transpose()?,
+                        ));
+                    }
+                    Ok(albums)
+                }
+            }
+        })
+        .await
+    }
+
+    /// Fetches the most listened album of each period in a time range. See [top_albums].
+    pub fn top_albums(
+        &self,
+        range: Range,
+        step: Step,
+        stepn: u32,
+    ) -> Result<Vec<(Range, Option<Album>)>, RequestError> {
+        let client = self.clone();
+        block_on(async move { client.top_albums_async(range, step, stepn).await })
+    }
+}
+
+/// See [top_artists].
+pub async fn top_artists_async(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<Vec<(Range, Option<Artist>)>, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .top_artists_async(range, step, stepn)
+        .await
+}
+
+/// Fetches the most listened artist of each period in a time range, e.g. the artist of every week.
+/// The artist is `None` for periods without any scrobbles.
+///
+/// # Arguments
+///
+/// * `range` - A time range.
+/// * `step` - The unit of time to divide the range into.
+/// * `stepn` - How many of those units each period spans.
+/// * `credentials` - Your credentials.
+///
+/// # Examples
+///
+/// ```no_run
+/// use mljcl::range::{Range, Step};
+///
+/// # let creds = mljcl::credentials::MalojaCredentialsBuilder::new().ip("127.0.0.1".into()).port(42010).build().unwrap();
+/// for (week, artist) in mljcl::top::top_artists(Range::ThisYear, Step::Week, 1, creds).unwrap() {
+///     if let Some(artist) = artist {
+///         println!("Artist of the week {}: {}", week, artist.name);
+///     }
+/// }
+/// ```
+pub fn top_artists(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+) -> Result<Vec<(Range, Option<Artist>)>, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .top_artists(range, step, stepn)
+}
+
+/// See [top_tracks].
+pub async fn top_tracks_async(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<Vec<(Range, Option<Track>)>, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .top_tracks_async(range, step, stepn)
+        .await
+}
+
+/// Fetches the most listened track of each period in a time range.
+/// The track is `None` for periods without any scrobbles. See [top_artists] for the arguments.
+pub fn top_tracks(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+) -> Result<Vec<(Range, Option<Track>)>, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .top_tracks(range, step, stepn)
+}
+
+/// See [top_albums].
+pub async fn top_albums_async(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+    client: Client,
+) -> Result<Vec<(Range, Option<Album>)>, RequestError> {
+    MalojaClient::with_client(credentials, client)
+        .top_albums_async(range, step, stepn)
+        .await
+}
+
+/// Fetches the most listened album of each period in a time range.
+/// The album is `None` for periods without any scrobbles. See [top_artists] for the arguments.
+pub fn top_albums(
+    range: Range,
+    step: Step,
+    stepn: u32,
+    credentials: MalojaCredentials,
+) -> Result<Vec<(Range, Option<Album>)>, RequestError> {
+    MalojaClient::new(credentials)
+        .map_err(RequestError::TransportError)?
+        .top_albums(range, step, stepn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::serve;
+
+    const JANUARY: &str = r#"{"fromstring":"2024/01","tostring":"2024/01","fromstamp":1704067200,"tostamp":1706745600,"description":"January 2024"}"#;
+    const FEBRUARY: &str = r#"{"fromstring":"2024/02","tostring":"2024/02","fromstamp":1706745600,"tostamp":1709251200,"description":"February 2024"}"#;
+
+    fn client(response: &str) -> MalojaClient {
+        MalojaClient::new(serve(vec![(200, response)]).0).unwrap()
+    }
+
+    #[test]
+    fn top_artists_of_each_period() {
+        let response = format!(
+            r#"{{"status":"ok","list":[
+                {{"range":{},"artist":"A","artist_id":7,"scrobbles":5}},
+                {{"range":{},"artist":null,"artist_id":null,"scrobbles":0}}
+            ]}}"#,
+            JANUARY, FEBRUARY
+        );
+        let top = client(&response)
+            .top_artists(Range::Year(2024), Step::Month, 1)
+            .unwrap();
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, Range::month(2024, 1).unwrap());
+        let artist = top[0].1.as_ref().unwrap();
+        assert_eq!((artist.name.as_str(), artist.id.as_str()), ("A", "7"));
+        assert_eq!(top[1].0, Range::month(2024, 2).unwrap());
+        assert!(top[1].1.is_none());
+    }
+
+    #[test]
+    fn top_artist_without_an_id_is_malformed() {
+        let response = format!(
+            r#"{{"status":"ok","list":[{{"range":{},"artist":"A","scrobbles":5}}]}}"#,
+            JANUARY
+        );
+        assert!(matches!(
+            client(&response).top_artists(Range::Year(2024), Step::Month, 1),
+            Err(RequestError::MalformedResponse(message)) if message.contains("`artist_id`")
+        ));
+    }
+}
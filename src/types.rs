@@ -1,4 +1,6 @@
-use crate::json::{TrackRes, TrackResultRes};
+use crate::errors::RequestError;
+use crate::json::{MedalsRes, TrackRes, TrackResultRes};
+use std::fmt;
 
 /// Track data.
 #[derive(Clone, Debug)]
@@ -73,3 +75,247 @@ pub struct Album {
     /// Optionally, a `Vec` of this album's artists.
     pub artists: Option<Vec<String>>,
 }
+
+/// The years in which an artist, track, or album was among the top three of the year.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Medals {
+    /// Years in which it was ranked first, as maloja describes them.
+    pub gold: Vec<String>,
+    /// Years in which it was ranked second.
+    pub silver: Vec<String>,
+    /// Years in which it was ranked third.
+    pub bronze: Vec<String>,
+}
+
+impl Medals {
+    #[allow(missing_docs)]
+    pub fn from_medalsres(medals: MedalsRes) -> Self {
+        Medals {
+            gold: medals.gold,
+            silver: medals.silver,
+            bronze: medals.bronze,
+        }
+    }
+}
+
+/// A certification maloja awards tracks and albums for reaching a number of scrobbles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Certification {
+    /// Gold.
+    Gold,
+    /// Platinum.
+    Platinum,
+    /// Diamond.
+    Diamond,
+}
+
+impl Certification {
+    /// Parses maloja's name for a certification, e.g. `"gold"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gold" => Some(Certification::Gold),
+            "platinum" => Some(Certification::Platinum),
+            "diamond" => Some(Certification::Diamond),
+            _ => None,
+        }
+    }
+}
+
+/// Details about an artist.
+#[derive(Debug, Clone)]
+pub struct ArtistInfo {
+    /// The artist.
+    pub artist: Artist,
+    /// The artist's total amount of scrobbles.
+    pub scrobbles: u64,
+    /// The artist's all-time chart rank.
+    pub position: Option<u64>,
+    /// Artists which are counted as this artist, e.g. members of a band.
+    pub associated: Vec<String>,
+    /// The artist's medals.
+    pub medals: Medals,
+    /// The amount of weeks in which this was the most listened artist.
+    pub top_weeks: u64,
+    /// If this artist only appears in the charts credited as another artist, that artist's name.
+    pub credited_as: Option<String>,
+}
+
+/// Details about a track.
+#[derive(Debug, Clone)]
+pub struct TrackInfo {
+    /// The track.
+    pub track: Track,
+    /// The track's total amount of scrobbles.
+    pub scrobbles: u64,
+    /// The track's all-time chart rank.
+    pub position: Option<u64>,
+    /// The track's medals.
+    pub medals: Medals,
+    /// The track's certification, if it has one.
+    pub certification: Option<Certification>,
+    /// The amount of weeks in which this was the most listened track.
+    pub top_weeks: u64,
+}
+
+/// Details about an album.
+#[derive(Debug, Clone)]
+pub struct AlbumInfo {
+    /// The album.
+    pub album: Album,
+    /// The album's total amount of scrobbles.
+    pub scrobbles: u64,
+    /// The album's all-time chart rank.
+    pub position: Option<u64>,
+    /// The album's medals.
+    pub medals: Medals,
+    /// The album's certification, if it has one.
+    pub certification: Option<Certification>,
+    /// The amount of weeks in which this was the most listened album.
+    pub top_weeks: u64,
+}
+
+/// An artist, track, or album to filter a query by, either by name or by its internal maloja ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entity {
+    /// An artist, by name.
+    Artist(String),
+    /// A track, by title and artists.
+    Track {
+        /// The title of the track.
+        title: String,
+        /// The track's artists.
+        artists: Vec<String>,
+    },
+    /// An album, by title and album artists.
+    Album {
+        /// The name of the album.
+        title: String,
+        /// The album's artists.
+        artists: Vec<String>,
+    },
+    /// An artist, by ID.
+    ArtistId(String),
+    /// A track, by ID.
+    TrackId(String),
+    /// An album, by ID.
+    AlbumId(String),
+}
+
+impl Entity {
+    // maloja takes lists of artists as repeated query parameters,
+    // which is why this isn't part of the serialized request structs.
+    pub(crate) fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        match self {
+            Entity::Artist(name) => vec![("artist", name.clone())],
+            Entity::Track { title, artists } => std::iter::once(("title", title.clone()))
+                .chain(artists.iter().map(|artist| ("trackartist", artist.clone())))
+                .collect(),
+            Entity::Album { title, artists } => std::iter::once(("albumtitle", title.clone()))
+                .chain(artists.iter().map(|artist| ("albumartist", artist.clone())))
+                .collect(),
+            Entity::ArtistId(id) => vec![("artist_id", id.clone())],
+            Entity::TrackId(id) => vec![("track_id", id.clone())],
+            Entity::AlbumId(id) => vec![("album_id", id.clone())],
+        }
+    }
+
+    // Whether this is an artist, track, or album, whichever way it is identified.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Entity::Artist(_) | Entity::ArtistId(_) => "artist",
+            Entity::Track { .. } | Entity::TrackId(_) => "track",
+            Entity::Album { .. } | Entity::AlbumId(_) => "album",
+        }
+    }
+}
+
+impl From<&Artist> for Entity {
+    fn from(artist: &Artist) -> Self {
+        Entity::ArtistId(artist.id.clone())
+    }
+}
+
+impl From<&Track> for Entity {
+    fn from(track: &Track) -> Self {
+        match &track.id {
+            Some(id) => Entity::TrackId(id.clone()),
+            None => Entity::Track {
+                title: track.name.clone(),
+                artists: track.artists.clone(),
+            },
+        }
+    }
+}
+
+impl From<&Album> for Entity {
+    fn from(album: &Album) -> Self {
+        Entity::AlbumId(album.id.clone())
+    }
+}
+
+// maloja's IDs are numbers, but are kept as strings in `Artist`, `Track`, `Album` and `Entity`.
+// Requests which change the database take these instead, so that only valid IDs can be sent.
+macro_rules! id_type {
+    ($($(#[$doc:meta])* $name:ident: $kind:literal)+) => {
+        $(
+            $(#[$doc])*
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $name(pub u64);
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{}", self.0)
+                }
+            }
+
+            impl std::str::FromStr for $name {
+                type Err = RequestError;
+
+                fn from_str(id: &str) -> Result<Self, Self::Err> {
+                    id.parse().map($name).map_err(|_| {
+                        RequestError::InvalidId(format!("{} ID `{}` is not a number", $kind, id))
+                    })
+                }
+            }
+        )+
+    };
+}
+
+id_type! {
+    /// The internal maloja ID of an artist.
+    ArtistId: "artist"
+    /// The internal maloja ID of a track.
+    TrackId: "track"
+    /// The internal maloja ID of an album.
+    AlbumId: "album"
+}
+
+impl TryFrom<&Artist> for ArtistId {
+    type Error = RequestError;
+
+    fn try_from(artist: &Artist) -> Result<Self, Self::Error> {
+        artist.id.parse()
+    }
+}
+
+impl TryFrom<&Track> for TrackId {
+    type Error = RequestError;
+
+    fn try_from(track: &Track) -> Result<Self, Self::Error> {
+        match &track.id {
+            Some(id) => id.parse(),
+            None => Err(RequestError::InvalidId(format!(
+                "track `{}` has no ID",
+                track.name
+            ))),
+        }
+    }
+}
+
+impl TryFrom<&Album> for AlbumId {
+    type Error = RequestError;
+
+    fn try_from(album: &Album) -> Result<Self, Self::Error> {
+        album.id.parse()
+    }
+}